@@ -1,6 +1,6 @@
 #![allow(clippy::identity_op)]
 
-use dune::{Color, Framebuffer, GlobeRenderer, Palette, SpriteSheet, draw_sprite_from_sheet};
+use dune::{Framebuffer, GlobeRenderer, PaletteFormat, SpriteSheet, draw_sprite_from_sheet};
 
 const MAP: &[u8] = include_bytes!("../assets/MAP.BIN");
 const GLOBDATA: &[u8] = include_bytes!("../assets/GLOBDATA.BIN");
@@ -12,17 +12,10 @@ const FRESK: &[u8] = include_bytes!("../assets/FRESK.BIN");
 const ICONES: &[u8] = include_bytes!("../assets/ICONES.BIN");
 
 fn main() -> Result<(), std::io::Error> {
-    let mut globe_renderer = GlobeRenderer::new(GLOBDATA, MAP, TABLAT);
+    let mut globe_renderer = GlobeRenderer::new(GLOBDATA, MAP, TABLAT)?;
 
-    let mut pal = Palette::new();
+    let pal = PaletteFormat::Act.read(PAL)?;
     let mut framebuffer = Framebuffer::new(320, 200);
-
-    for i in 0..256 {
-        let r = ((PAL[3 * i + 0] as u32) * 63 / 255) as u8;
-        let g = ((PAL[3 * i + 1] as u32) * 63 / 255) as u8;
-        let b = ((PAL[3 * i + 2] as u32) * 63 / 255) as u8;
-        pal.set(i, Color(r, g, b));
-    }
     framebuffer.clear();
 
     let rotation = 0;
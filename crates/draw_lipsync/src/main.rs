@@ -35,15 +35,8 @@ fn main() {
             if is_compressed {
                 let compressed_data = std::fs::read(&path)
                     .unwrap_or_else(|_| panic!("Unable to read file `{path:?}`"));
-                let mut r = Cursor::new(&compressed_data);
 
-                let header = hsq::Header::from_reader(&mut r).unwrap();
-
-                let mut data = Vec::with_capacity(header.uncompressed_size() as usize);
-                let mut w = Cursor::new(&mut data);
-
-                hsq::unhsq(&mut r, &mut w).expect("Unable to decompress file");
-                data
+                hsq::decompress(&compressed_data).expect("Unable to decompress file")
             } else {
                 std::fs::read(&path).expect("Unable to read file")
             }
@@ -59,7 +52,7 @@ fn main() {
         let sprite_sheet = SpriteSheet::from_slice(&data).unwrap();
         let last_resource_id = sprite_sheet.resource_count() - 1;
         let lipsync_data = sprite_sheet.get_resource(last_resource_id).unwrap();
-        let lipsync = Lipsync::from_bytes(lipsync_data);
+        let lipsync = Lipsync::from_bytes(lipsync_data).unwrap();
 
         let mut pal = Palette::new();
         sprite_sheet.apply_palette_update(&mut pal).unwrap();
@@ -93,12 +86,14 @@ fn main() {
 
                 framebuffer.clear();
 
-                lipsync.draw_animation_frame(
-                    &mut framebuffer,
-                    &sprite_sheet,
-                    last_animation_idx,
-                    v as usize + 1,
-                );
+                lipsync
+                    .draw_animation_frame(
+                        &mut framebuffer,
+                        &sprite_sheet,
+                        last_animation_idx,
+                        v as usize + 1,
+                    )
+                    .unwrap();
 
                 framebuffer
                     .write_ppm_scaled(&pal, &format!("out/{output_file_stem}-voc-{index:02}.ppm"))
@@ -110,7 +105,9 @@ fn main() {
                 for j in 0..lipsync.animation_frame_count(i) {
                     framebuffer.clear();
 
-                    lipsync.draw_animation_frame(&mut framebuffer, &sprite_sheet, i, j);
+                    lipsync
+                        .draw_animation_frame(&mut framebuffer, &sprite_sheet, i, j)
+                        .unwrap();
 
                     framebuffer
                         .write_ppm_scaled(
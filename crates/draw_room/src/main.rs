@@ -1,4 +1,4 @@
-use dune::{DrawOptions, Framebuffer, Palette, RoomRenderer, RoomSheet, SpriteSheet};
+use dune::{DrawOptions, Framebuffer, Palette, RoomRenderer, RoomSheet, Sky, SpriteSheet};
 
 static ROOMS_SHEET: &[u8] = include_bytes!("../../../assets/PALACE.SAL");
 static SPRITE_SHEET: &[u8] = include_bytes!("../../../assets/POR.BIN");
@@ -8,13 +8,17 @@ fn main() {
     let room_sheet = RoomSheet::new(ROOMS_SHEET).unwrap();
     let room = room_sheet.get_room(1).unwrap();
     let sprite_sheet = SpriteSheet::from_slice(SPRITE_SHEET).unwrap();
+    let sky = Sky::from_slice(SKYDN).unwrap();
 
     let mut pal = Palette::new();
     let mut framebuffer = Framebuffer::new(320, 200);
 
     let mut room_renderer = RoomRenderer::new();
 
-    room_renderer.draw_sky(SKYDN, 8, &mut pal);
+    room_renderer.set_game_time(4, 0);
+    room_renderer
+        .draw_sky(&sky, &mut pal, &mut framebuffer)
+        .unwrap();
 
     sprite_sheet.apply_palette_update(&mut pal).unwrap();
 
@@ -25,6 +29,7 @@ fn main() {
         .draw(
             &DrawOptions {
                 draw_sprites: true,
+                draw_characters: true,
                 draw_polygons: true,
                 draw_lines: true,
             },
@@ -1,8 +1,7 @@
-use std::{fs::read, io::Cursor, path::PathBuf};
+use std::{fs::read, path::PathBuf};
 
-use bytes_ext::ReadBytesExt;
 use clap::Parser;
-use dune::{Color, Framebuffer, Palette, SpriteSheet, draw_sprite};
+use dune::{Framebuffer, Palette, Sky, SpriteSheet, draw_sprite};
 
 #[derive(Parser, Debug)] // requires `derive` feature
 struct Args {
@@ -43,7 +42,7 @@ fn main() {
     sprite_sheet.apply_palette_update(&mut pal).unwrap();
 
     if let Some(sky) = args.sky {
-        apply_sky_palette(sky, &mut pal);
+        Sky::from_slice(SKYDN).unwrap().apply_palette(sky, &mut pal);
     }
 
     draw_sprite(sprite, 0, 0, &mut framebuffer).unwrap();
@@ -59,23 +58,3 @@ fn main() {
         eprintln!("{err:?}");
     };
 }
-
-fn apply_sky_palette(palette_index: usize, pal: &mut Palette) {
-    let mut c = Cursor::new(SKYDN);
-
-    let toc_pos = c.read_le_u16().unwrap() as u64;
-    c.set_position(toc_pos + (8 + palette_index.min(32) as u64) * 2);
-
-    let sub_ofs = c.read_le_u16().unwrap() as u64;
-    c.set_position(toc_pos + sub_ofs + 6);
-
-    let pal_ofs = 73;
-    let pal_cnt = 151;
-    for i in 0..pal_cnt {
-        let r = c.read_u8().unwrap();
-        let g = c.read_u8().unwrap();
-        let b = c.read_u8().unwrap();
-
-        pal.set(pal_ofs + i, Color(r, g, b));
-    }
-}
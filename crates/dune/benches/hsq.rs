@@ -0,0 +1,128 @@
+#![feature(test)]
+
+extern crate test;
+
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use dune::hsq;
+use test::{Bencher, black_box};
+
+/// A 320x200 frame with large flat areas, dithering and noise, similar to
+/// the full frames in the intro videos.
+fn large_frame() -> Vec<u8> {
+    let mut seed = 0x1234_5678u32;
+    let mut frame = Vec::with_capacity(320 * 200);
+    for y in 0..200u32 {
+        for x in 0..320u32 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let c = match y {
+                0..60 => 0x10 + (y / 8) as u8,
+                60..140 => 0x40 + ((x + y) & 1) as u8 + (x / 40) as u8,
+                _ => 0x80 + (seed >> 28) as u8,
+            };
+            frame.push(c);
+        }
+    }
+    frame
+}
+
+fn compressed_frame() -> Vec<u8> {
+    hsq::compress(&large_frame()).unwrap()
+}
+
+/// The original decoder, which seeks the output for every copied byte.
+fn unhsq_seek<R: Read, W: Read + Write + Seek>(mut r: R, w: &mut W) -> std::io::Result<()> {
+    let mut queue = 0u16;
+    let mut read_bit = |r: &mut R| -> std::io::Result<bool> {
+        let mut bit = (queue & 1) == 1;
+        queue >>= 1;
+        if queue == 0 {
+            let mut b = [0; 2];
+            r.read_exact(&mut b)?;
+            queue = u16::from_le_bytes(b);
+            bit = (queue & 1) == 1;
+            queue = 0x8000 | (queue >> 1);
+        }
+        Ok(bit)
+    };
+    let read_u8 = |r: &mut R| -> std::io::Result<u8> {
+        let mut b = [0; 1];
+        r.read_exact(&mut b)?;
+        Ok(b[0])
+    };
+
+    let mut w_ofs = 0u64;
+    loop {
+        if read_bit(&mut r)? {
+            w.seek(SeekFrom::Start(w_ofs))?;
+            w.write_all(&[read_u8(&mut r)?])?;
+            w_ofs += 1;
+        } else {
+            let mut count: u16;
+            let offset: u16;
+            if read_bit(&mut r)? {
+                let word = u16::from_le_bytes([read_u8(&mut r)?, read_u8(&mut r)?]);
+                count = word & 7;
+                offset = 8192 - (word >> 3);
+                if count == 0 {
+                    count = read_u8(&mut r)? as u16;
+                }
+                if count == 0 {
+                    break;
+                }
+            } else {
+                let b0 = read_bit(&mut r)? as u16;
+                let b1 = read_bit(&mut r)? as u16;
+
+                count = 2 * b0 + b1;
+                offset = 256 - (read_u8(&mut r)? as u16);
+            }
+
+            let src_ofs = w_ofs - offset as u64;
+            for i in 0..count as u64 + 2 {
+                w.seek(SeekFrom::Start(src_ofs + i))?;
+                let mut b = [0; 1];
+                w.read_exact(&mut b)?;
+
+                w.seek(SeekFrom::Start(w_ofs))?;
+                w.write_all(&b)?;
+                w_ofs += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[bench]
+fn bench_unhsq_seek(b: &mut Bencher) {
+    let compressed = compressed_frame();
+    let mut buffer = Vec::new();
+    b.iter(|| {
+        let mut w = Cursor::new(&mut buffer);
+        unhsq_seek(black_box(&compressed[6..]), &mut w).unwrap();
+    });
+    assert_eq!(buffer, large_frame());
+}
+
+#[bench]
+fn bench_unhsq_vec(b: &mut Bencher) {
+    let compressed = compressed_frame();
+    let mut buffer = Vec::new();
+    b.iter(|| {
+        buffer.clear();
+        hsq::unhsq(black_box(&compressed[6..]), &mut buffer).unwrap();
+    });
+    assert_eq!(buffer, large_frame());
+}
+
+#[bench]
+fn bench_decoder_read(b: &mut Bencher) {
+    let compressed = compressed_frame();
+    let mut buffer = vec![0; 320 * 200];
+    b.iter(|| {
+        let mut decoder = hsq::Decoder::new(black_box(&compressed[6..]));
+        decoder.read_exact(&mut buffer).unwrap();
+    });
+    assert_eq!(buffer, large_frame());
+}
@@ -0,0 +1,21 @@
+#![no_main]
+
+use dune::{Framebuffer, Palette, hnm::HnmDecoder};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut pal = Palette::new();
+    let Ok(mut hnm_decoder) = HnmDecoder::new(data, &mut pal) else {
+        return;
+    };
+
+    let mut framebuffer = Framebuffer::new(320, 200);
+    for frame in 0..hnm_decoder.frame_count() {
+        if hnm_decoder
+            .decode_frame(frame, &mut framebuffer, &mut pal)
+            .is_err()
+        {
+            break;
+        }
+    }
+});
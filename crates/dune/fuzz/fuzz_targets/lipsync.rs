@@ -0,0 +1,18 @@
+#![no_main]
+
+use dune::{Framebuffer, Lipsync, SpriteSheet};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(lipsync) = Lipsync::from_bytes(data) else {
+        return;
+    };
+
+    let sprite_sheet = SpriteSheet::default();
+    let mut framebuffer = Framebuffer::new(320, 200);
+    for i in 0..lipsync.animation_count() {
+        for j in 0..lipsync.animation_frame_count(i) {
+            _ = lipsync.draw_animation_frame(&mut framebuffer, &sprite_sheet, i, j);
+        }
+    }
+});
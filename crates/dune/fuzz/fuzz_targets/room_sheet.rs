@@ -0,0 +1,28 @@
+#![no_main]
+
+use dune::{DrawOptions, Framebuffer, IndexMap, RoomRenderer, RoomSheet, SpriteSheet};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(room_sheet) = RoomSheet::new(data) else {
+        return;
+    };
+
+    let mut framebuffer = Framebuffer::new(320, 200);
+    let mut index_map = IndexMap::new();
+
+    for i in 0..room_sheet.room_count() {
+        let Some(room) = room_sheet.get_room(i) else {
+            continue;
+        };
+
+        let mut room_renderer = RoomRenderer::new();
+        room_renderer.set_room(room.clone());
+        room_renderer.set_sprite_sheet(SpriteSheet::default());
+        _ = room_renderer.draw(
+            &DrawOptions::default(),
+            &mut framebuffer,
+            Some(&mut index_map),
+        );
+    }
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use dune::{Framebuffer, Palette, SpriteSheet, draw_sprite};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(sprite_sheet) = SpriteSheet::from_possibly_compressed_slice(data) else {
+        return;
+    };
+
+    let mut pal = Palette::new();
+    _ = sprite_sheet.apply_palette_update(&mut pal);
+
+    let mut framebuffer = Framebuffer::new(320, 200);
+    for id in 0..sprite_sheet.resource_count() {
+        if let Some(sprite) = sprite_sheet.get_sprite(id) {
+            _ = draw_sprite(sprite, 0, 0, &mut framebuffer);
+        }
+    }
+});
@@ -4,12 +4,13 @@ use bytes_ext::U32Ext;
 
 use crate::{
     Color, Framebuffer, Palette, Rect, SpriteSheet, attack::countdown_timer::CountdownTimer,
-    draw_sprite, draw_sprite_from_sheet, sprite_blitter,
+    draw_sprite, draw_sprite_from_sheet, palette_fader::transition_palette, sprite_blitter,
 };
 
 const MAX_PARTICLES: usize = 64;
 const INITIAL_POSITIONS: [(i16, i16); 4] = [(125, 101), (100, 101), (239, 122), (271, 125)];
 const INITIAL_VELOCITIES: [(i8, i8); 4] = [(-6, 4), (-4, 6), (-4, -6), (-6, -4)];
+const SKY_PALETTE_RANGE: std::ops::Range<usize> = 128..128 + 28;
 
 pub struct Screen {
     pal: Palette,
@@ -545,18 +546,15 @@ impl AttackState {
             palette_idx -= 1;
 
             if self.timers.timer7.get() != 10 {
-                // self.gfx_vtable_func_39_transition_palette(al, bx, cx, dl);
-
-                for i in 128..128 + 28 {
-                    let divisor = self.timers.timer7.get().max(1) as i16;
-
-                    let c0 = self.gfx.pal_5bf.get(i);
-                    let c1 = self.gfx.pal_2bf.get(i);
-
-                    let rgb = c0.lerp(c1, divisor);
-
-                    self.gfx.pal_5bf.set(i, rgb);
-                    self.screen.pal.set(i, rgb);
+                let divisor = self.timers.timer7.get().max(1) as u16;
+                transition_palette(
+                    &mut self.gfx.pal_5bf,
+                    &self.gfx.pal_2bf,
+                    SKY_PALETTE_RANGE,
+                    divisor,
+                );
+                for i in SKY_PALETTE_RANGE {
+                    self.screen.pal.set(i, self.gfx.pal_5bf.get(i));
                 }
 
                 return;
@@ -728,17 +726,6 @@ impl AttackState {
         }
     }
 
-    // fn gfx_vtable_func_39_transition_palette(&mut self, al: u8, bx: u16, cx: u16, dl: u8) {
-    //     let speed = u16::max(al as u16, 1);
-    //     let offset = bx;
-    //     let count = cx;
-
-    //     for i in 0..count {
-    //         // let d = src[i] - dst[i];
-    //         // dst[i] += d / speed;
-    //     }
-    // }
-
     fn sub_1c6ad_particles_update_dirty_rect(&mut self, dirty_rect: &Rect) {
         self.sub_1c13b_open_onmap_resource();
 
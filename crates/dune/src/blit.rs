@@ -147,6 +147,10 @@ fn draw(
     let bpp = if pal_offset >= 254 { 8 } else { 4 };
     let pitch = pitch(bpp, width);
 
+    if !rle && data.len() < pitch as usize * height as usize {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+    }
+
     if scale != 0 {
         if bpp != 4 {
             return Ok(());
@@ -244,7 +248,7 @@ fn draw_4bpp_scaled(
 
                 let x = x as u16;
                 let y = y as u16;
-                frame.set(x, y, c + pal_offset);
+                frame.set(x, y, c.wrapping_add(pal_offset));
                 if let Some(index_map) = index_map.as_deref_mut() {
                     index_map.set_index(x, y, index);
                 }
@@ -292,7 +296,7 @@ fn draw_4bpp(
 
                 let x = x as u16;
                 let y = y as u16;
-                frame.set(x, y, c + pal_offset);
+                frame.set(x, y, c.wrapping_add(pal_offset));
                 if let Some(index_map) = index_map.as_deref_mut() {
                     index_map.set_index(x, y, index);
                 }
@@ -375,7 +379,46 @@ fn unrle(data: &[u8], pitch: u16, height: u16) -> std::io::Result<Vec<u8>> {
     Ok(buf)
 }
 
-fn pitch(bpp: u8, width: u16) -> u16 {
+/// Run-length encodes rows of `pitch` bytes in the format read by `unrle`.
+pub(crate) fn rle(data: &[u8], pitch: u16) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    for row in data.chunks(pitch as usize) {
+        let mut literal_start = 0;
+        let mut x = 0;
+        while x < row.len() {
+            let run = row[x..]
+                .iter()
+                .take(128)
+                .take_while(|&&c| c == row[x])
+                .count();
+
+            // Runs of two cost the same as literals and would split them
+            if run < 3 {
+                x += 1;
+                continue;
+            }
+
+            rle_literals(&row[literal_start..x], &mut out);
+            out.push((257 - run) as u8);
+            out.push(row[x]);
+            x += run;
+            literal_start = x;
+        }
+        rle_literals(&row[literal_start..], &mut out);
+    }
+
+    out
+}
+
+fn rle_literals(literals: &[u8], out: &mut Vec<u8>) {
+    for chunk in literals.chunks(128) {
+        out.push((chunk.len() - 1) as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+pub(crate) fn pitch(bpp: u8, width: u16) -> u16 {
     assert!(width > 0);
     if bpp == 8 {
         width
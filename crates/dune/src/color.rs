@@ -10,6 +10,8 @@ impl Color {
         Color(r as u8, g as u8, b as u8)
     }
 
+    /// Moves `1/divisor` of the way to `other`, truncating towards `self`
+    /// like the game's palette transitions.
     pub fn lerp(self, other: Color, divisor: i16) -> Self {
         let (r0, g0, b0) = self.as_i16();
         let (r1, g1, b1) = other.as_i16();
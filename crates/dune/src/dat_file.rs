@@ -1,38 +1,89 @@
 use std::{
     fs::File,
-    io::{BufReader, Cursor, ErrorKind, Read, Seek},
+    io::{BufReader, BufWriter, Cursor, ErrorKind, Read, Seek, Write},
     path::Path,
 };
 
-use bytes_ext::ReadBytesExt;
+use bytes_ext::{ReadBytesExt, WriteBytesExt};
 
-use crate::hsq;
+use crate::{Error, ResourceKind, hsq};
 
-pub struct DatFile {
-    reader: BufReader<File>,
+pub struct DatFile<R = BufReader<File>> {
+    reader: R,
     pub entries: Vec<DatEntry>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DatEntry {
     pub name: String,
     pub offset: usize,
     pub size: usize,
 }
 
-type Error = std::io::Error;
+/// A DAT entry together with what it contains.
+#[derive(Debug)]
+pub struct CatalogEntry {
+    pub entry: DatEntry,
+    pub kind: ResourceKind,
+    pub compressed: bool,
+    pub uncompressed_size: usize,
+    /// Why the entry couldn't be read or decompressed, in which case its
+    /// kind is unknown.
+    pub error: Option<Error>,
+}
+
+const NAME_LEN: usize = 16;
+const ENTRY_LEN: usize = NAME_LEN + 4 + 4 + 1;
 
 impl DatFile {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<DatFile, Error> {
         let file = File::open(path)?;
-        let mut reader = BufReader::new(file);
+        DatFile::new(BufReader::new(file))
+    }
+}
+
+impl<'a> DatFile<Cursor<&'a [u8]>> {
+    /// Opens a DAT archive that is already in memory, e.g. fetched by a
+    /// browser or memory-mapped by the caller.
+    pub fn from_slice(data: &'a [u8]) -> Result<Self, Error> {
+        DatFile::new(Cursor::new(data))
+    }
+
+    /// Returns the raw bytes of an entry without copying them.
+    pub fn raw_slice(&self, name: &str) -> Result<&'a [u8], Error> {
+        let entry = self.find_entry(name)?;
+        let data: &'a [u8] = self.reader.get_ref();
+
+        let Some(end) = entry.offset.checked_add(entry.size) else {
+            return Err(Error::BadOffset {
+                resource: name.to_owned(),
+                offset: entry.offset,
+            });
+        };
 
-        let entry_count = reader.read_le_u16()? as usize;
+        data.get(entry.offset..end).ok_or_else(|| Error::Truncated {
+            resource: name.to_owned(),
+            offset: data.len(),
+        })
+    }
+}
+
+impl<R: Read + Seek> DatFile<R> {
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        const RESOURCE: &str = "dat file";
+
+        let entry_count = reader
+            .read_le_u16()
+            .map_err(|err| Error::from_read_error(err, RESOURCE, 0))?
+            as usize;
         let mut entries = Vec::with_capacity(entry_count);
-        for _ in 0..entry_count {
-            let name = reader.read_fixed_str(16)?;
-            let size = reader.read_le_u32()? as usize;
-            let offset = reader.read_le_u32()? as usize;
+        for i in 0..entry_count {
+            let entry_ofs = 2 + i * ENTRY_LEN;
+            let map_err = |err| Error::from_read_error(err, RESOURCE, entry_ofs);
+
+            let name = reader.read_fixed_str(NAME_LEN).map_err(map_err)?;
+            let size = reader.read_le_u32().map_err(map_err)? as usize;
+            let offset = reader.read_le_u32().map_err(map_err)? as usize;
             _ = reader.read_u8();
 
             if name.is_empty() {
@@ -45,18 +96,26 @@ impl DatFile {
         Ok(DatFile { reader, entries })
     }
 
+    pub fn entry(&self, name: &str) -> Option<&DatEntry> {
+        self.entries.iter().find(|&e| e.name == name)
+    }
+
+    fn find_entry(&self, name: &str) -> Result<&DatEntry, Error> {
+        self.entry(name).ok_or_else(|| Error::NotFound {
+            resource: name.to_owned(),
+        })
+    }
+
     pub fn read_raw(&mut self, name: &str) -> Result<Vec<u8>, Error> {
-        let entry = self
-            .entries
-            .iter()
-            .find(|&e| e.name == name)
-            .ok_or(Error::from(ErrorKind::NotFound))?;
+        let entry = self.find_entry(name)?;
+        let (offset, size) = (entry.offset, entry.size);
 
-        self.reader
-            .seek(std::io::SeekFrom::Start(entry.offset as u64))?;
+        self.reader.seek(std::io::SeekFrom::Start(offset as u64))?;
 
-        let mut data = vec![0; entry.size];
-        self.reader.read_exact(data.as_mut_slice())?;
+        let mut data = vec![0; size];
+        self.reader
+            .read_exact(data.as_mut_slice())
+            .map_err(|err| Error::from_read_error(err, name, offset))?;
 
         Ok(data)
     }
@@ -64,22 +123,251 @@ impl DatFile {
     pub fn read(&mut self, name: &str) -> Result<Vec<u8>, Error> {
         let data = self.read_raw(name)?;
 
-        let mut reader = Cursor::new(&data);
-        let header = hsq::Header::from_reader(&mut reader)?;
+        let Ok(header) = hsq::Header::from_reader(&mut Cursor::new(&data)) else {
+            return Ok(data);
+        };
 
         if !header.is_compressed() {
             return Ok(data);
         }
 
-        if header.compressed_size() as usize != data.len() {
-            println!("Packed length does not match resource size");
-            return Ok(data);
+        hsq::decompress(&data).map_err(|err| err.in_resource(name))
+    }
+
+    /// Reads every entry and classifies it, in archive order.
+    ///
+    /// Entries that fail to read or decompress are still listed, with the
+    /// error recorded in [`CatalogEntry::error`].
+    pub fn catalog(&mut self) -> Vec<CatalogEntry> {
+        let entries = self.entries.clone();
+        entries
+            .into_iter()
+            .map(|entry| self.catalog_entry(entry))
+            .collect()
+    }
+
+    fn catalog_entry(&mut self, entry: DatEntry) -> CatalogEntry {
+        let mut catalog_entry = CatalogEntry {
+            kind: ResourceKind::Unknown,
+            compressed: false,
+            uncompressed_size: 0,
+            error: None,
+            entry,
+        };
+        let name = &catalog_entry.entry.name;
+
+        let raw = match self.read_raw(name) {
+            Ok(raw) => raw,
+            Err(err) => {
+                catalog_entry.error = Some(err);
+                return catalog_entry;
+            }
+        };
+
+        let header = hsq::Header::from_reader(&mut Cursor::new(&raw))
+            .ok()
+            .filter(|header| header.is_compressed());
+        catalog_entry.compressed = header.is_some();
+        catalog_entry.uncompressed_size =
+            header.map_or(raw.len(), |header| header.uncompressed_size() as usize);
+
+        let data = if catalog_entry.compressed {
+            match hsq::decompress(&raw) {
+                Ok(data) => data,
+                Err(err) => {
+                    catalog_entry.error = Some(err.in_resource(name));
+                    return catalog_entry;
+                }
+            }
+        } else {
+            raw
+        };
+
+        catalog_entry.kind = ResourceKind::detect(name, &data);
+        catalog_entry.uncompressed_size = data.len();
+        catalog_entry
+    }
+}
+
+/// Builds a DAT archive from named entries.
+///
+/// Entries are written in the order they are added, followed by an empty
+/// entry that terminates the table.
+#[derive(Default)]
+pub struct DatWriter {
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl DatWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Adds an entry that is stored as-is.
+    pub fn add_raw(&mut self, name: &str, data: Vec<u8>) -> std::io::Result<()> {
+        if name.is_empty() || name.len() > NAME_LEN || !name.is_ascii() {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid entry name `{name}`"),
+            ));
+        }
+        if self.entries.iter().any(|(n, _)| n == name) {
+            return Err(std::io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!("duplicate entry name `{name}`"),
+            ));
+        }
+
+        self.entries.push((name.to_owned(), data));
+        Ok(())
+    }
+
+    /// Adds an entry that is HSQ-compressed before being stored.
+    pub fn add_compressed(&mut self, name: &str, data: &[u8]) -> std::io::Result<()> {
+        let data = hsq::compress(data)?;
+        self.add_raw(name, data)
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let entry_count = self.entries.len() + 1;
+        let table_size = 2 + entry_count * ENTRY_LEN;
+
+        w.write_le_u16(entry_count.try_into().map_err(|_| {
+            std::io::Error::new(ErrorKind::InvalidInput, "too many entries for dat file")
+        })?)?;
+
+        let mut offset = table_size;
+        for (name, data) in &self.entries {
+            let mut name_bytes = [0u8; NAME_LEN];
+            name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+
+            let size: u32 = data.len().try_into().map_err(|_| {
+                std::io::Error::new(ErrorKind::InvalidInput, format!("entry `{name}` too large"))
+            })?;
+            let ofs: u32 = offset
+                .try_into()
+                .map_err(|_| std::io::Error::new(ErrorKind::InvalidInput, "dat file too large"))?;
+
+            w.write_all(&name_bytes)?;
+            w.write_all(&size.to_le_bytes())?;
+            w.write_all(&ofs.to_le_bytes())?;
+            w.write_u8(0)?;
+
+            offset += data.len();
         }
+        w.write_all(&[0; ENTRY_LEN])?;
+
+        for (_, data) in &self.entries {
+            w.write_all(data)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_open() {
+        let sprite_data: Vec<u8> = (0..4000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut writer = DatWriter::new();
+        writer.add_raw("RAW.BIN", vec![1, 2, 3, 4]).unwrap();
+        writer.add_compressed("PACKED.HSQ", &sprite_data).unwrap();
+        assert!(writer.add_raw("RAW.BIN", vec![]).is_err());
+        assert!(writer.add_raw("A_VERY_LONG_NAME.BIN", vec![]).is_err());
+
+        let path = std::env::temp_dir().join(format!("dat-writer-{}.dat", std::process::id()));
+        writer.write_to_file(&path).unwrap();
+
+        let mut dat_file = DatFile::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let names: Vec<&str> = dat_file.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["RAW.BIN", "PACKED.HSQ"]);
+
+        assert_eq!(dat_file.read("RAW.BIN").unwrap(), [1, 2, 3, 4]);
+        assert_eq!(dat_file.read("PACKED.HSQ").unwrap(), sprite_data);
+        assert_ne!(dat_file.read_raw("PACKED.HSQ").unwrap(), sprite_data);
+    }
 
-        let mut unpacked_data = vec![0; header.uncompressed_size() as usize];
-        let mut writer = Cursor::new(&mut unpacked_data);
+    #[test]
+    fn test_from_slice() {
+        let mut writer = DatWriter::new();
+        writer.add_raw("A.BIN", vec![1, 2, 3, 4, 5, 6, 7]).unwrap();
+        writer.add_compressed("B.HSQ", &[9; 100]).unwrap();
 
-        hsq::unhsq(&data[6..], &mut writer).unwrap();
-        Ok(unpacked_data)
+        let mut data = Vec::new();
+        writer.write(&mut data).unwrap();
+
+        let mut dat_file = DatFile::from_slice(&data).unwrap();
+        let a = dat_file.raw_slice("A.BIN").unwrap();
+        assert_eq!(a, [1, 2, 3, 4, 5, 6, 7]);
+        assert!(std::ptr::eq(a.as_ptr(), &data[dat_file.entries[0].offset]));
+
+        assert_eq!(dat_file.read("B.HSQ").unwrap(), [9; 100]);
+        assert!(dat_file.raw_slice("C.BIN").is_err());
+
+        data.truncate(data.len() - 1);
+        let dat_file = DatFile::from_slice(&data).unwrap();
+        assert!(dat_file.raw_slice("B.HSQ").is_err());
+
+        // An entry whose end overflows, as a corrupt table can on 32-bit
+        let mut dat_file = DatFile::from_slice(&data).unwrap();
+        dat_file.entries[0].offset = usize::MAX - 2;
+        assert!(matches!(
+            dat_file.raw_slice("A.BIN"),
+            Err(Error::BadOffset { .. })
+        ));
+    }
+
+    #[test]
+    fn test_catalog() {
+        let sprite_sheet = [0x02, 0x00, 0x02, 0x00, 0x02, 0x00, 0x01, 0x00, 0x12];
+
+        let mut writer = DatWriter::new();
+        writer.add_compressed("ORNY.HSQ", &sprite_sheet).unwrap();
+        writer.add_raw("PALACE.SAL", vec![0x02, 0x00]).unwrap();
+        writer.add_raw("README.TXT", b"hello".to_vec()).unwrap();
+        let truncated = hsq::Header::new(100, 6).header.to_vec();
+        writer.add_raw("BROKEN.HSQ", truncated).unwrap();
+
+        let mut data = Vec::new();
+        writer.write(&mut data).unwrap();
+
+        let catalog = DatFile::from_slice(&data).unwrap().catalog();
+        let summary: Vec<_> = catalog
+            .iter()
+            .map(|e| {
+                (
+                    e.entry.name.as_str(),
+                    e.kind,
+                    e.compressed,
+                    e.uncompressed_size,
+                    e.error.is_some(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("ORNY.HSQ", ResourceKind::SpriteSheet, true, 9, false),
+                ("PALACE.SAL", ResourceKind::RoomSheet, false, 2, false),
+                ("README.TXT", ResourceKind::Unknown, false, 5, false),
+                ("BROKEN.HSQ", ResourceKind::Unknown, true, 100, true),
+            ]
+        );
     }
 }
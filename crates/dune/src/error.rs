@@ -0,0 +1,158 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    IoError(std::io::Error),
+    NotFound {
+        resource: String,
+    },
+    Truncated {
+        resource: String,
+        offset: usize,
+    },
+    BadChecksum {
+        resource: String,
+        checksum: u8,
+    },
+    SizeMismatch {
+        resource: String,
+        expected: usize,
+        actual: usize,
+    },
+    BadOffset {
+        resource: String,
+        offset: usize,
+    },
+    UnknownBlockType {
+        resource: String,
+        offset: usize,
+        block_type: u16,
+    },
+    InvalidData {
+        resource: String,
+        offset: usize,
+        reason: &'static str,
+    },
+    TooLarge {
+        resource: String,
+        width: usize,
+        height: usize,
+    },
+}
+
+impl Error {
+    /// Converts an error from reading `resource` at `offset`, reporting
+    /// running out of data as [`Error::Truncated`].
+    pub(crate) fn from_read_error(err: std::io::Error, resource: &str, offset: usize) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => Error::Truncated {
+                resource: resource.to_owned(),
+                offset,
+            },
+            _ => Error::IoError(err),
+        }
+    }
+
+    /// The name of the resource that failed to parse.
+    ///
+    /// Parsers that don't know which file they are reading use the kind of
+    /// data instead, e.g. "sprite sheet". Use [`Error::in_resource`] to
+    /// replace it with the actual name.
+    pub fn resource(&self) -> Option<&str> {
+        match self {
+            Error::IoError(_) => None,
+            Error::NotFound { resource }
+            | Error::Truncated { resource, .. }
+            | Error::BadChecksum { resource, .. }
+            | Error::SizeMismatch { resource, .. }
+            | Error::BadOffset { resource, .. }
+            | Error::UnknownBlockType { resource, .. }
+            | Error::InvalidData { resource, .. }
+            | Error::TooLarge { resource, .. } => Some(resource),
+        }
+    }
+
+    pub fn in_resource(mut self, name: &str) -> Self {
+        match &mut self {
+            Error::IoError(_) => {}
+            Error::NotFound { resource }
+            | Error::Truncated { resource, .. }
+            | Error::BadChecksum { resource, .. }
+            | Error::SizeMismatch { resource, .. }
+            | Error::BadOffset { resource, .. }
+            | Error::UnknownBlockType { resource, .. }
+            | Error::InvalidData { resource, .. }
+            | Error::TooLarge { resource, .. } => *resource = name.to_owned(),
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::IoError(err) => write!(f, "{err}"),
+            Error::NotFound { resource } => write!(f, "{resource}: not found"),
+            Error::Truncated { resource, offset } => {
+                write!(f, "{resource}: truncated data at offset {offset:#x}")
+            }
+            Error::BadChecksum { resource, checksum } => {
+                write!(f, "{resource}: bad checksum {checksum:#04x}")
+            }
+            Error::SizeMismatch {
+                resource,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{resource}: size mismatch, expected {expected} bytes, got {actual} bytes"
+            ),
+            Error::BadOffset { resource, offset } => {
+                write!(f, "{resource}: bad offset {offset:#x}")
+            }
+            Error::UnknownBlockType {
+                resource,
+                offset,
+                block_type,
+            } => write!(
+                f,
+                "{resource}: unknown block type {block_type:#06x} at offset {offset:#x}"
+            ),
+            Error::InvalidData {
+                resource,
+                offset,
+                reason,
+            } => write!(f, "{resource}: {reason} at offset {offset:#x}"),
+            Error::TooLarge {
+                resource,
+                width,
+                height,
+            } => write!(f, "{resource}: {width}x{height} is too large"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::IoError(error)
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::IoError(err) => err,
+            Error::NotFound { .. } => std::io::Error::new(std::io::ErrorKind::NotFound, error),
+            _ => std::io::Error::new(std::io::ErrorKind::InvalidData, error),
+        }
+    }
+}
@@ -4,11 +4,46 @@ use std::{
     path::Path,
 };
 
-use crate::{Palette, image::Image};
+use crate::{Error, Palette, RgbaImage, RgbaOptions, image::Image, scaler::Scaler};
 
 pub type Framebuffer = Image<u8>;
 
 impl Framebuffer {
+    /// Converts the image to RGBA, scaled by an integer factor and
+    /// optionally stretched to a 4:3 aspect ratio.
+    pub fn to_rgba(&self, pal: &Palette, scale: u16, aspect: bool) -> Result<RgbaImage, Error> {
+        let mut image = RgbaImage::new(0, 0);
+        let options = RgbaOptions {
+            scaler: Scaler::new(scale).aspect(aspect),
+            ..RgbaOptions::default()
+        };
+        self.write_rgba(pal, options, &mut image)?;
+        Ok(image)
+    }
+
+    /// Converts the image to RGBA into `image`, which is only reallocated
+    /// if its size changes. Fails with [`Error::TooLarge`] if the scaled
+    /// size doesn't fit in an image.
+    pub fn write_rgba(
+        &self,
+        pal: &Palette,
+        options: RgbaOptions,
+        image: &mut RgbaImage,
+    ) -> Result<(), Error> {
+        let mut colors = [[0u8; 4]; 256];
+        for (i, color) in colors.iter_mut().enumerate() {
+            let rgb = pal.get_rgb888(i);
+            *color = [rgb.0, rgb.1, rgb.2, 255];
+        }
+        if options.transparent_zero {
+            colors[0][3] = 0;
+        }
+
+        options
+            .scaler
+            .scale_map(self, image, |c| colors[c as usize])
+    }
+
     pub fn write_ppm(&self, pal: &Palette, filename: &str) -> std::io::Result<()> {
         let width = self.w as usize;
         let height = self.h as usize;
@@ -28,65 +63,65 @@ impl Framebuffer {
     }
 
     pub fn write_ppm_scaled(&self, pal: &Palette, filename: &str) -> std::io::Result<()> {
-        const SCALE_X: usize = 5;
-        const SCALE_Y: usize = 6;
-
-        let src_width = self.w as usize;
-        let dst_width = src_width * SCALE_X;
-        let dst_height = self.h as usize * SCALE_Y;
-
-        let mut data = Vec::with_capacity(3 * dst_width * dst_height);
-
-        for src_y in 0..self.h as usize {
-            let src_row = &self.pixels[src_y * src_width..(src_y + 1) * src_width];
-
-            for _ in 0..SCALE_Y {
-                for &pixel in src_row {
-                    let rgb = pal.get_rgb888(pixel as usize);
-                    for _ in 0..SCALE_X {
-                        data.push(rgb.0);
-                        data.push(rgb.1);
-                        data.push(rgb.2);
-                    }
-                }
-            }
-        }
+        self.write_ppm_with(pal, Scaler::new(5).aspect(true), filename)
+    }
+
+    /// Writes the image as a PPM file, scaled by `scaler`.
+    pub fn write_ppm_with(
+        &self,
+        pal: &Palette,
+        scaler: Scaler,
+        filename: &str,
+    ) -> std::io::Result<()> {
+        let mut image = RgbaImage::new(0, 0);
+        let options = RgbaOptions {
+            scaler,
+            ..RgbaOptions::default()
+        };
+        self.write_rgba(pal, options, &mut image)?;
+        let data: Vec<u8> = image
+            .pixels()
+            .iter()
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect();
 
         let mut f = BufWriter::new(File::create(filename)?);
-        writeln!(f, "P6 {} {} 255", dst_width, dst_height)?;
+        writeln!(f, "P6 {} {} 255", image.w(), image.h())?;
         f.write_all(&data)?;
         Ok(())
     }
 
     pub fn write_png_scaled<P: AsRef<Path>>(&self, pal: &Palette, path: P) -> std::io::Result<()> {
-        self.write_png_scaled_(pal, path.as_ref())
+        self.write_png(pal, Scaler::new(5).aspect(true), path)
     }
 
-    fn write_png_scaled_(&self, pal: &Palette, path: &Path) -> std::io::Result<()> {
+    /// Writes the image as a PNG file, scaled by `scaler`.
+    pub fn write_png<P: AsRef<Path>>(
+        &self,
+        pal: &Palette,
+        scaler: Scaler,
+        path: P,
+    ) -> std::io::Result<()> {
+        self.write_png_(pal, scaler, path.as_ref())
+    }
+
+    fn write_png_(&self, pal: &Palette, scaler: Scaler, path: &Path) -> std::io::Result<()> {
         let file = File::create(path)?;
         let w = &mut BufWriter::new(file);
 
-        let expanded_width = 5 * self.w as usize;
-        let expanded_height = 6 * self.h as usize;
-        let mut rgba_data = vec![0u8; expanded_width * expanded_height * 4];
-
-        for y in 0..expanded_height {
-            for x in 0..expanded_width {
-                let src_idx = (y / 6) * self.w as usize + (x / 5);
-                let c = self.pixels[src_idx] as usize;
-                let rgb = pal.get_rgb888(c);
-                rgba_data[4 * (y * expanded_width + x) + 0] = rgb.0;
-                rgba_data[4 * (y * expanded_width + x) + 1] = rgb.1;
-                rgba_data[4 * (y * expanded_width + x) + 2] = rgb.2;
-            }
-        }
+        let mut image = RgbaImage::new(0, 0);
+        let options = RgbaOptions {
+            scaler,
+            ..RgbaOptions::default()
+        };
+        self.write_rgba(pal, options, &mut image)?;
 
-        let mut encoder = png::Encoder::new(w, expanded_width as u32, expanded_height as u32);
+        let mut encoder = png::Encoder::new(w, image.w() as u32, image.h() as u32);
         encoder.set_color(png::ColorType::Rgba);
         encoder.set_depth(png::BitDepth::Eight);
 
         let mut writer = encoder.write_header()?;
-        writer.write_image_data(&rgba_data)?;
+        writer.write_image_data(image.as_bytes())?;
 
         Ok(())
     }
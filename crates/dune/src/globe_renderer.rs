@@ -2,9 +2,16 @@ use std::io::Cursor;
 
 use bytes_ext::ReadBytesExt;
 
-use crate::Framebuffer;
+use crate::{Error, Framebuffer, resource_reader::ResourceReader};
 
 const MAX_TILT: usize = 99;
+const GLOBDATA_TABLES_OFFSET: usize = 3290;
+const GLOBDATA_TABLES_SIZE: usize = 64 * 200;
+/// Offset of the equator in MAP. Rows of the northern hemisphere are
+/// stored before it and rows of the southern one after it.
+const MAP_CENTER: usize = 0x62FC;
+/// Width of the map at the equator, in map cells.
+const MAP_WIDTH: usize = 398;
 
 #[derive(Copy, Clone, Debug, Default)]
 struct RotationEntry {
@@ -53,7 +60,16 @@ pub struct GlobeRenderer {
 }
 
 impl GlobeRenderer {
-    pub fn new(globdata: &[u8], map: &[u8], tablat: &[u8]) -> GlobeRenderer {
+    pub fn new(globdata: &[u8], map: &[u8], tablat: &[u8]) -> Result<GlobeRenderer, Error> {
+        let max_line_len = validate_globdata(globdata)?;
+
+        if tablat.len() < 8 * MAX_TILT {
+            return Err(Error::Truncated {
+                resource: "tablat".to_owned(),
+                offset: tablat.len(),
+            });
+        }
+
         let mut r = GlobeRenderer {
             globdata: globdata.to_vec(),
             map: map.to_vec(),
@@ -82,30 +98,85 @@ impl GlobeRenderer {
             e.map_row_len = u16::from_be_bytes(tablat[offset + 2..offset + 4].try_into().unwrap());
         }
 
+        r.validate_tables(max_line_len, map.len())?;
+
         r.precalculate_globe_rotation_lookup_table(0);
-        r
+        Ok(r)
+    }
+
+    /// Checks that every rotation table row and map offset `draw` can look
+    /// up for lines of up to `max_line_len` pixels is within the tables.
+    fn validate_tables(&self, max_line_len: usize, map_len: usize) -> Result<(), Error> {
+        for x in 0..max_line_len {
+            for latitude in 0..MAX_TILT {
+                let offset = GLOBDATA_TABLES_OFFSET + x * 200 + latitude;
+                let bad_offset = |offset| Error::BadOffset {
+                    resource: "globdata".to_owned(),
+                    offset,
+                };
+
+                let row = self.globdata_table_2(x, latitude) as usize / 2;
+                let Some(e) = self.rotation_lookup_table.get(row) else {
+                    return Err(bad_offset(offset));
+                };
+
+                let column = self.globdata_table_3(x, latitude);
+                if !(0..=e.map_row_len as i16).contains(&column) {
+                    return Err(bad_offset(offset + 100));
+                }
+            }
+        }
+
+        for (i, e) in self.rotation_lookup_table.iter().enumerate() {
+            // The offset into a row wraps at twice its length, except at the
+            // equator where the rotation itself is the offset.
+            let row_width = if i == 0 {
+                MAP_WIDTH.max(2 * e.map_row_len as usize)
+            } else {
+                2 * e.map_row_len as usize
+            };
+            let row_start = e.map_row_start as usize;
+
+            if e.map_row_start < 0
+                || row_start > MAP_CENTER
+                || 2 * e.map_row_len as usize > MAP_WIDTH
+            {
+                return Err(Error::BadOffset {
+                    resource: "tablat".to_owned(),
+                    offset: 8 * i,
+                });
+            }
+            if MAP_CENTER + row_start + row_width > map_len {
+                return Err(Error::Truncated {
+                    resource: "map".to_owned(),
+                    offset: map_len,
+                });
+            }
+        }
+
+        Ok(())
     }
 
     fn globdata_table_2(&self, x: usize, latitude: usize) -> u8 {
         assert!(x < 64);
         assert!(latitude < 100);
-        self.globdata[3290 + x * 200 + latitude]
+        self.globdata[GLOBDATA_TABLES_OFFSET + x * 200 + latitude]
     }
 
     fn globdata_table_3(&self, x: usize, latitude: usize) -> i16 {
         assert!(x < 64);
         assert!(latitude < 100);
-        self.globdata[3290 + x * 200 + latitude + 100] as i8 as i16
+        self.globdata[GLOBDATA_TABLES_OFFSET + x * 200 + latitude + 100] as i8 as i16
     }
 
     fn precalculate_globe_rotation_lookup_table(&mut self, rotation: u16) {
-        let mut dxax: u32 = 398 * rotation as u32;
+        let mut dxax: u32 = MAP_WIDTH as u32 * rotation as u32;
         dxax &= !0xffff;
 
         self.rotation_lookup_table[0].fp = dxax;
         dxax += 0x8000;
 
-        let bx = dxax / 398;
+        let bx = dxax / MAP_WIDTH as u32;
         for i in 1..self.rotation_lookup_table.len() {
             let dxax = 2 * bx * self.rotation_lookup_table[i].map_row_len as u32;
 
@@ -114,7 +185,7 @@ impl GlobeRenderer {
     }
 
     fn map_color(&self, offset: i16) -> u8 {
-        let map_value = self.map[(0x62FCi32 + offset as i32) as usize];
+        let map_value = self.map[(MAP_CENTER as i32 + offset as i32) as usize];
         let flags = (map_value >> 4) & 3;
         let mut color = map_value & 0x0f;
 
@@ -221,3 +292,95 @@ impl GlobeRenderer {
         self.draw_half(fb, Half::Lower, tilt);
     }
 }
+
+/// Checks that the line data in `globdata` stays within the bounds that
+/// `GlobeRenderer::draw` relies on. Returns the length of the longest line.
+fn validate_globdata(globdata: &[u8]) -> Result<usize, Error> {
+    if globdata.len() < GLOBDATA_TABLES_OFFSET + GLOBDATA_TABLES_SIZE {
+        return Err(Error::Truncated {
+            resource: "globdata".to_owned(),
+            offset: globdata.len(),
+        });
+    }
+
+    let mut r = ResourceReader::new("globdata", globdata);
+    let mut max_line_len = 0;
+    let mut line_count = 0;
+
+    loop {
+        let n = r.read_i8()?;
+        if n >= 0 {
+            return Err(r.invalid_data("invalid line length"));
+        }
+
+        let line_len = !n as usize;
+        if line_len == 0 {
+            break;
+        }
+        if line_len > 64 {
+            return Err(r.invalid_data("line too long"));
+        }
+        line_count += 1;
+        if line_count > 80 {
+            return Err(r.invalid_data("too many lines"));
+        }
+        max_line_len = max_line_len.max(line_len);
+
+        for _ in 0..line_len {
+            let n = r.read_i8()?;
+            if !(-99..=99).contains(&n) {
+                return Err(r.invalid_data("latitude out of range"));
+            }
+        }
+    }
+
+    Ok(max_line_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_tables() {
+        // A single line one pixel long, drawn at latitude 0
+        let mut globdata = vec![0; GLOBDATA_TABLES_OFFSET + GLOBDATA_TABLES_SIZE];
+        globdata[..3].copy_from_slice(&[0xfe, 0x00, 0xff]);
+        let tablat = [0; 8 * MAX_TILT];
+        let map = vec![0x05; MAP_CENTER + MAP_WIDTH];
+
+        let mut globe_renderer = GlobeRenderer::new(&globdata, &map, &tablat).unwrap();
+        let mut fb = Framebuffer::new(320, 200);
+        globe_renderer.draw(&mut fb, 0, 0);
+        assert_eq!(fb.get(159, 79), 0x15);
+
+        assert!(matches!(
+            GlobeRenderer::new(&globdata, &map[..map.len() - 1], &tablat),
+            Err(Error::Truncated { .. })
+        ));
+
+        let mut bad_tablat = tablat;
+        bad_tablat[8 * 3 + 2..8 * 3 + 4].copy_from_slice(&200u16.to_be_bytes());
+        assert!(matches!(
+            GlobeRenderer::new(&globdata, &map, &bad_tablat),
+            Err(Error::BadOffset { offset: 24, .. })
+        ));
+
+        let mut bad_globdata = globdata.clone();
+        bad_globdata[GLOBDATA_TABLES_OFFSET] = 2 * MAX_TILT as u8;
+        assert!(matches!(
+            GlobeRenderer::new(&bad_globdata, &map, &tablat),
+            Err(Error::BadOffset {
+                offset: GLOBDATA_TABLES_OFFSET,
+                ..
+            })
+        ));
+
+        let mut bad_globdata = globdata.clone();
+        bad_globdata[GLOBDATA_TABLES_OFFSET + 100] = 1;
+        assert!(matches!(
+            GlobeRenderer::new(&bad_globdata, &map, &tablat),
+            Err(Error::BadOffset { .. })
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+const VOC_MAGIC: &[u8] = b"Creative Voice File\x1a";
+const VOC_BLOCK_SOUND_DATA: u8 = 1;
+
+/// Sample rate used when the first sound block has no header.
+pub const DEFAULT_SAMPLE_RATE: u32 = 11025;
+
+/// Format of the PCM data in an HNM's "sd" blocks.
+///
+/// The blocks hold unsigned 8-bit samples, [`AudioSamples`] converts them
+/// to signed 16-bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+}
+
+impl Default for AudioFormat {
+    fn default() -> Self {
+        Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            channels: 1,
+            bits_per_sample: 8,
+        }
+    }
+}
+
+/// Parses the header at the start of the first sound block, if any.
+///
+/// Returns the format and the number of header bytes to skip. The header
+/// is either a complete Creative Voice file header or just a VOC sound data
+/// block header.
+pub(crate) fn parse_sound_header(data: &[u8]) -> (AudioFormat, usize) {
+    let mut pos = 0;
+
+    if data.starts_with(VOC_MAGIC) {
+        let Some(&[lo, hi]) = data.get(20..22) else {
+            return (AudioFormat::default(), 0);
+        };
+        pos = u16::from_le_bytes([lo, hi]) as usize;
+    }
+
+    match data.get(pos..pos + 6) {
+        Some(&[VOC_BLOCK_SOUND_DATA, _, _, _, time_constant, 0]) => {
+            let format = AudioFormat {
+                sample_rate: 1_000_000 / (256 - time_constant as u32),
+                ..AudioFormat::default()
+            };
+            (format, pos + 6)
+        }
+        _ if pos > 0 => (AudioFormat::default(), pos.min(data.len())),
+        _ => (AudioFormat::default(), 0),
+    }
+}
+
+/// The PCM samples of one frame as signed 16-bit values.
+#[derive(Debug, Clone)]
+pub struct AudioSamples<'a> {
+    data: std::slice::Iter<'a, u8>,
+}
+
+impl<'a> AudioSamples<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data: data.iter() }
+    }
+
+    /// The raw unsigned 8-bit samples.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data.as_slice()
+    }
+}
+
+impl Iterator for AudioSamples<'_> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        self.data.next().map(|&s| ((s as i16) - 128) << 8)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.data.size_hint()
+    }
+}
+
+impl ExactSizeIterator for AudioSamples<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sound_header() {
+        assert_eq!(
+            parse_sound_header(&[0x80, 0x81, 0x7f]),
+            (AudioFormat::default(), 0)
+        );
+
+        let (format, skip) = parse_sound_header(&[0x01, 0x10, 0x00, 0x00, 0xa6, 0x00, 0x80]);
+        assert_eq!(format.sample_rate, 11111);
+        assert_eq!(skip, 6);
+
+        let mut voc = b"Creative Voice File\x1a\x1a\x00\x0a\x01\x29\x11".to_vec();
+        voc.extend_from_slice(&[0x01, 0x10, 0x00, 0x00, 0x83, 0x00, 0x80]);
+        let (format, skip) = parse_sound_header(&voc);
+        assert_eq!(format.sample_rate, 8000);
+        assert_eq!(skip, 32);
+
+        let samples: Vec<i16> = AudioSamples::new(&[0x00, 0x80, 0xff]).collect();
+        assert_eq!(samples, [-32768, 0, 32512]);
+    }
+}
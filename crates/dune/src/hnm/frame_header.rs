@@ -1,6 +1,6 @@
 use std::io::Read;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct FrameHeader {
     w: u16,
     h: u8,
@@ -0,0 +1,360 @@
+//! The HNM4 layout used by later Cryo titles.
+//!
+//! Files use the header and superchunks of all signed HNM files, see
+//! [`super::signed`]. Each superchunk holds chunks with an 8-byte header:
+//! the chunk size in the same format as the superchunk's, a two-letter
+//! uppercase type and two flag bytes. Chunks of other types, e.g. the
+//! sound of other titles, are skipped.
+//!
+//! Pictures are stored with each pair of rows interleaved, the pixel of
+//! the upper row followed by the one below it.
+
+use crate::{
+    Error, Framebuffer, Palette, Rect,
+    hnm::{
+        hnm_decoder::{FrameBlocks, FrameChanges},
+        signed::read_size,
+    },
+    resource_reader::ResourceReader,
+};
+
+const RESOURCE: &str = "hnm";
+
+const CHUNK_HEADER_SIZE: usize = 8;
+
+/// Palette update, in the same format as Dune's.
+const CHUNK_TYPE_PL: u16 = 0x504C;
+/// Keyframe, compressed with [`hlz_decompress`].
+const CHUNK_TYPE_IZ: u16 = 0x495A;
+/// Frame built from the two previous ones, see
+/// [`Hnm4Buffers::decode_interframe`].
+const CHUNK_TYPE_IU: u16 = 0x4955;
+/// Sound, which isn't decoded, see [`FrameBlocks::undecoded_sound`].
+const CHUNK_TYPE_SD: u16 = 0x5344;
+
+struct Chunk<'a> {
+    pos: usize,
+    chunk_type: u16,
+    data: &'a [u8],
+}
+
+fn read_chunk<'a>(r: &mut ResourceReader<'a>) -> Result<Chunk<'a>, Error> {
+    let pos = r.position();
+    let size = read_size(r)?;
+    let chunk_type = r.read_be_u16()?;
+    // Flags
+    r.skip(2)?;
+
+    let data = r.read_slice(
+        size.checked_sub(CHUNK_HEADER_SIZE)
+            .ok_or_else(|| r.bad_offset(pos))?,
+    )?;
+
+    Ok(Chunk {
+        pos,
+        chunk_type,
+        data,
+    })
+}
+
+pub(super) fn frame_blocks(mut r: ResourceReader) -> Result<FrameBlocks, Error> {
+    let mut blocks = FrameBlocks::default();
+
+    while !r.remaining().is_empty() {
+        let chunk = read_chunk(&mut r)?;
+        match chunk.chunk_type {
+            CHUNK_TYPE_PL => blocks.palette_updates.push(chunk.data),
+            CHUNK_TYPE_IZ => blocks.is_keyframe = true,
+            CHUNK_TYPE_SD => blocks.undecoded_sound = true,
+            _ => {}
+        }
+    }
+
+    Ok(blocks)
+}
+
+/// The two interleaved pictures HNM4 frames are decoded into.
+///
+/// Each frame is decoded into the buffer of the frame before the previous
+/// one, so pixels it skips keep their contents from two frames ago.
+pub(super) struct Hnm4Buffers {
+    width: u16,
+    current: Vec<u8>,
+    previous: Vec<u8>,
+}
+
+impl Hnm4Buffers {
+    pub fn new(width: u16, height: u16) -> Self {
+        let size = width as usize * height as usize;
+        Self {
+            width,
+            current: vec![0; size],
+            previous: vec![0; size],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.current.fill(0);
+        self.previous.fill(0);
+    }
+
+    pub fn decode_chunks<'a>(
+        &mut self,
+        mut r: ResourceReader<'a>,
+        framebuffer: &mut Framebuffer,
+        pal: &mut Palette,
+    ) -> Result<FrameChanges<'a>, Error> {
+        let mut changes = FrameChanges {
+            rect: Rect::default(),
+            is_full_frame: false,
+            palette_changed: false,
+            sound: None,
+        };
+
+        while !r.remaining().is_empty() {
+            let chunk = read_chunk(&mut r)?;
+
+            match chunk.chunk_type {
+                CHUNK_TYPE_PL => {
+                    pal.apply_palette_update(chunk.data)?;
+                    changes.palette_changed = true;
+                }
+                CHUNK_TYPE_IZ => {
+                    let compressed = chunk.data.get(4..).ok_or_else(|| Error::Truncated {
+                        resource: RESOURCE.to_owned(),
+                        offset: chunk.pos + CHUNK_HEADER_SIZE,
+                    })?;
+                    hlz_decompress(compressed, &mut self.current)?;
+                    self.previous.copy_from_slice(&self.current);
+
+                    changes.rect = self.draw(framebuffer);
+                    changes.is_full_frame = true;
+                }
+                CHUNK_TYPE_IU => {
+                    std::mem::swap(&mut self.current, &mut self.previous);
+                    self.decode_interframe(chunk.data)?;
+
+                    changes.rect = self.draw(framebuffer);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Decodes a frame made of two-pixel runs, copied from either buffer.
+    ///
+    /// Each opcode holds a count in its low 5 bits and flags in the rest. A
+    /// count of 0 selects a command from the flags:
+    ///
+    /// * 0: store the next two pixels
+    /// * 1, 2: skip a u8 or u16 count of pixel pairs
+    /// * 3: fill one line of the screen with the next byte
+    /// * 4 to 7: end of frame
+    ///
+    /// Otherwise `count` pixel pairs are copied, going backwards if bit 2 is
+    /// set, from two rows up if bit 1 is set and from the previous frame if
+    /// bit 0 is set. The source follows as a u16, the offset from the current
+    /// position plus 0x8000, with bit 0 set to swap the pixels of each pair.
+    fn decode_interframe(&mut self, data: &[u8]) -> Result<(), Error> {
+        let mut r = ResourceReader::new(RESOURCE, data);
+        let width = self.width as usize;
+        let mut pos = 0;
+
+        loop {
+            let opcode = r.read_u8()?;
+            let count = (opcode & 0x1f) as usize;
+            let flags = opcode >> 5;
+
+            if count == 0 {
+                match flags {
+                    0 => {
+                        let pixels = r.read_slice(2)?;
+                        self.current_slice(&r, pos, 2)?.copy_from_slice(pixels);
+                        pos += 2;
+                    }
+                    1 => pos += 2 * r.read_u8()? as usize,
+                    2 => pos += 2 * r.read_le_u16()? as usize,
+                    3 => {
+                        let color = r.read_u8()?;
+                        self.current_slice(&r, pos, width)?.fill(color);
+                        pos += width;
+                    }
+                    _ => break,
+                }
+                continue;
+            }
+
+            let backward = flags & 4 != 0;
+            let from_row_above = flags & 2 != 0;
+            let from_previous = flags & 1 != 0;
+
+            let source = r.read_le_u16()? as usize;
+            let swap = source & 1 != 0;
+            let mut src = (pos + (source & 0xfffe))
+                .checked_sub(0x8000)
+                .ok_or_else(|| r.invalid_data("copy before start of frame"))?
+                as isize;
+
+            let (mut shift0, mut shift1) = if from_row_above {
+                (1 - 2 * width as isize, 0)
+            } else {
+                (0, 1)
+            };
+            if swap {
+                std::mem::swap(&mut shift0, &mut shift1);
+            }
+
+            for _ in 0..count {
+                let src_buffer = if from_previous {
+                    &self.previous
+                } else {
+                    &self.current
+                };
+                let get = |ofs: isize| {
+                    usize::try_from(ofs)
+                        .ok()
+                        .and_then(|ofs| src_buffer.get(ofs).copied())
+                        .ok_or_else(|| r.invalid_data("copy outside of frame"))
+                };
+                let pixels = [get(src + shift0)?, get(src + shift1)?];
+
+                self.current_slice(&r, pos, 2)?.copy_from_slice(&pixels);
+                pos += 2;
+                src += if backward { -2 } else { 2 };
+            }
+        }
+
+        Ok(())
+    }
+
+    fn current_slice(
+        &mut self,
+        r: &ResourceReader,
+        pos: usize,
+        len: usize,
+    ) -> Result<&mut [u8], Error> {
+        self.current
+            .get_mut(pos..pos + len)
+            .ok_or_else(|| r.invalid_data("write outside of frame"))
+    }
+
+    /// Draws the current picture at the top left corner of the framebuffer.
+    fn draw(&self, framebuffer: &mut Framebuffer) -> Rect {
+        let width = self.width as usize;
+
+        for (i, row_pair) in self.current.chunks_exact(2 * width).enumerate() {
+            let y = 2 * i as u16;
+            if y + 1 >= framebuffer.h() {
+                break;
+            }
+            for (x, pixels) in row_pair.chunks_exact(2).enumerate() {
+                let x = x as u16;
+                if x >= framebuffer.w() {
+                    break;
+                }
+                framebuffer.set(x, y, pixels[0]);
+                framebuffer.set(x, y + 1, pixels[1]);
+            }
+        }
+
+        let height = self.current.len() / width;
+        Rect {
+            x0: 0,
+            y0: 0,
+            x1: width.min(framebuffer.w() as usize) as i16,
+            y1: height.min(framebuffer.h() as usize) as i16,
+        }
+    }
+}
+
+/// Reads bits from the most significant bit of 32-bit words, which are
+/// interleaved with the bytes they describe.
+struct BitReader<'a> {
+    r: ResourceReader<'a>,
+    queue: u32,
+    queue_len: u8,
+}
+
+impl BitReader<'_> {
+    fn read_bit(&mut self) -> Result<bool, Error> {
+        if self.queue_len == 0 {
+            self.queue = self.r.read_le_u32()?;
+            self.queue_len = 32;
+        }
+        let bit = self.queue & 0x8000_0000 != 0;
+        self.queue <<= 1;
+        self.queue_len -= 1;
+        Ok(bit)
+    }
+}
+
+/// Decompresses the LZ77 variant used by HNM4 keyframes into `dst`.
+///
+/// The tokens are the same as HSQ's, but the bit queue is 32 bits wide and
+/// read from the most significant bit.
+fn hlz_decompress(src: &[u8], dst: &mut [u8]) -> Result<(), Error> {
+    let mut bits = BitReader {
+        r: ResourceReader::new(RESOURCE, src),
+        queue: 0,
+        queue_len: 0,
+    };
+    let mut pos = 0;
+
+    loop {
+        if bits.read_bit()? {
+            let b = bits.r.read_u8()?;
+            *dst.get_mut(pos)
+                .ok_or_else(|| bits.r.invalid_data("frame data too large"))? = b;
+            pos += 1;
+            continue;
+        }
+
+        let (count, distance) = if bits.read_bit()? {
+            let word = bits.r.read_le_u16()? as usize;
+            let mut count = word & 7;
+            if count == 0 {
+                count = bits.r.read_u8()? as usize;
+                if count == 0 {
+                    break;
+                }
+            }
+            (count, 0x2000 - (word >> 3))
+        } else {
+            let count = 2 * bits.read_bit()? as usize + bits.read_bit()? as usize;
+            (count, 0x100 - bits.r.read_u8()? as usize)
+        };
+
+        let count = count + 2;
+        let src_pos = pos
+            .checked_sub(distance)
+            .ok_or_else(|| bits.r.invalid_data("back-reference before start of data"))?;
+        if pos + count > dst.len() {
+            return Err(bits.r.invalid_data("frame data too large"));
+        }
+        for i in 0..count {
+            dst[pos + i] = dst[src_pos + i];
+        }
+        pos += count;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hlz_decompress() {
+        // Two literals, a short copy of the two and the end marker
+        let src = [0x00, 0x00, 0x00, 0xc1, 0x01, 0x02, 0xfe, 0x00, 0x00, 0x00];
+        let mut dst = [0; 4];
+        hlz_decompress(&src, &mut dst).unwrap();
+        assert_eq!(dst, [1, 2, 1, 2]);
+
+        let mut dst = [0; 3];
+        assert!(hlz_decompress(&src, &mut dst).is_err());
+    }
+}
@@ -1,45 +1,378 @@
-use std::io::{Cursor, Seek};
+use crate::{
+    Error, Framebuffer, Palette, Rect, blit,
+    hnm::{
+        audio::{AudioFormat, AudioSamples, parse_sound_header},
+        frame_header::FrameHeader,
+        hnm4::{self, Hnm4Buffers},
+        signed,
+        version::HnmVersion,
+    },
+    hsq,
+    resource_reader::ResourceReader,
+};
 
-use bytes_ext::ReadBytesExt;
+const RESOURCE: &str = "hnm";
 
-use crate::{Framebuffer, Palette, blit, hnm::frame_header::FrameHeader, hsq};
+const BLOCK_TYPE_SD: u16 = 0x7364;
+const BLOCK_TYPE_PL: u16 = 0x706C;
 
+const SCREEN_WIDTH: u16 = 320;
+const SCREEN_HEIGHT: u16 = 200;
+
+/// Decodes HNM videos.
+///
+/// Frames can be decoded one at a time onto a caller-provided framebuffer
+/// with [`HnmDecoder::decode_frame`], or played back by iterating over the
+/// decoder, which keeps its own framebuffer and palette. [`HnmDecoder::seek`]
+/// moves the playback position using the keyframes.
+///
+/// Dune's videos and HNM1 and HNM4 files are decoded, see [`HnmVersion`].
+/// The sound of HNM4 files isn't, see [`HnmDecoder::has_undecoded_audio`].
 pub struct HnmDecoder<'a> {
     data: &'a [u8],
-    header_size: u16,
+    version: HnmVersion,
+    /// Where the frames start, `frame_offsets` are relative to it.
+    frames_start: usize,
     frame_offsets: Vec<u32>,
-    buffer: Vec<u8>,
+    frame_decoder: FrameDecoder,
+    audio: Option<AudioStream>,
+    undecoded_audio: bool,
+    keyframes: Vec<usize>,
+
+    initial_palette: Palette,
+    palette: Palette,
+    framebuffer: Framebuffer,
+    next_frame: usize,
+}
+
+/// Where the audio stream starts.
+struct AudioStream {
+    format: AudioFormat,
+    first_frame: usize,
+    header_size: usize,
+}
+
+/// A frame returned by the [`HnmDecoder`] iterator.
+#[derive(Clone)]
+pub struct DecodedFrame {
+    pub index: usize,
+    pub is_full_frame: bool,
+    /// The area of the screen changed by this frame. Empty if the frame has
+    /// no video.
+    pub rect: Rect,
+    /// The screen after applying this frame.
+    pub framebuffer: Framebuffer,
+    /// The complete palette after this frame, if the frame changed it.
+    pub palette: Option<Palette>,
+    pub audio: Vec<i16>,
+}
+
+/// The blocks of a frame, found without decoding it.
+#[derive(Default)]
+pub(super) struct FrameBlocks<'a> {
+    pub sound: Option<&'a [u8]>,
+    pub palette_updates: Vec<&'a [u8]>,
+    pub is_keyframe: bool,
+    /// The frame has sound in a format that isn't decoded.
+    pub undecoded_sound: bool,
+}
+
+/// What decoding a frame changed.
+pub(super) struct FrameChanges<'a> {
+    pub rect: Rect,
+    pub is_full_frame: bool,
+    pub palette_changed: bool,
+    pub sound: Option<&'a [u8]>,
+}
+
+/// The frame decoder for an [`HnmVersion`], with the buffers it keeps
+/// between frames.
+enum FrameDecoder {
+    /// Dune's frames, using the buffer for decompressing them.
+    Dune(Vec<u8>),
+    /// Dune's frames in the superchunks of a signed file.
+    Hnm1(Vec<u8>),
+    Hnm4(Hnm4Buffers),
+}
+
+impl FrameDecoder {
+    /// Reads the size at the start of a frame, which includes the size
+    /// itself.
+    fn read_frame_size(&self, r: &mut ResourceReader) -> Result<usize, Error> {
+        match self {
+            FrameDecoder::Dune(_) => Ok(r.read_le_u16()? as usize),
+            FrameDecoder::Hnm1(_) | FrameDecoder::Hnm4(_) => signed::read_size(r),
+        }
+    }
+
+    fn frame_blocks<'a>(&self, r: ResourceReader<'a>) -> Result<FrameBlocks<'a>, Error> {
+        match self {
+            FrameDecoder::Dune(_) | FrameDecoder::Hnm1(_) => find_blocks(r),
+            FrameDecoder::Hnm4(_) => hnm4::frame_blocks(r),
+        }
+    }
+
+    fn decode<'a>(
+        &mut self,
+        r: ResourceReader<'a>,
+        framebuffer: &mut Framebuffer,
+        pal: &mut Palette,
+    ) -> Result<FrameChanges<'a>, Error> {
+        match self {
+            FrameDecoder::Dune(buffer) | FrameDecoder::Hnm1(buffer) => {
+                decode_blocks(r, buffer, framebuffer, pal)
+            }
+            FrameDecoder::Hnm4(buffers) => buffers.decode_chunks(r, framebuffer, pal),
+        }
+    }
+
+    /// Forgets earlier frames before decoding restarts at a keyframe.
+    fn reset(&mut self) {
+        if let FrameDecoder::Hnm4(buffers) = self {
+            buffers.clear();
+        }
+    }
 }
 
 impl<'a> HnmDecoder<'a> {
-    pub fn new(data: &'a [u8], pal: &mut Palette) -> std::io::Result<Self> {
-        let mut r = Cursor::new(data);
+    /// Parses the header of a video and finds its keyframes.
+    ///
+    /// Signed versions other than HNM1 and HNM4 are rejected.
+    pub fn new(data: &'a [u8], pal: &mut Palette) -> Result<Self, Error> {
+        let mut decoder = match HnmVersion::detect(data) {
+            HnmVersion::Dune => Self::new_dune(data, pal)?,
+            HnmVersion::Signed(version @ (1 | 4)) => Self::new_signed(data, version, pal)?,
+            HnmVersion::Signed(_) => {
+                return Err(Error::InvalidData {
+                    resource: RESOURCE.to_owned(),
+                    offset: 3,
+                    reason: "unsupported hnm version",
+                });
+            }
+        };
+
+        for frame in 0..decoder.frame_count() {
+            // Broken frames are reported when they are decoded
+            let Ok(blocks) = decoder.frame_blocks(frame) else {
+                break;
+            };
+
+            if let (None, Some(sound_data)) = (&decoder.audio, blocks.sound) {
+                let (format, header_size) = parse_sound_header(sound_data);
+                decoder.audio = Some(AudioStream {
+                    format,
+                    first_frame: frame,
+                    header_size,
+                });
+            }
+
+            decoder.undecoded_audio |= blocks.undecoded_sound;
+            if blocks.is_keyframe {
+                decoder.keyframes.push(frame);
+            }
+        }
+
+        Ok(decoder)
+    }
+
+    fn new_dune(data: &'a [u8], pal: &mut Palette) -> Result<Self, Error> {
+        let mut r = ResourceReader::new(RESOURCE, data);
         let header_size = r.read_le_u16()?;
 
-        let pal_size = pal.apply_palette_update(&data[2..])?;
-        r.seek_relative(pal_size as i64)?;
+        let pal_size = pal.apply_palette_update(r.remaining())?;
+        r.skip(pal_size as usize)?;
         let toc_pos = r.position();
 
-        let frame_count = (header_size as u64 - toc_pos) / 4;
+        if (header_size as usize) < toc_pos {
+            return Err(r.bad_offset(header_size as usize));
+        }
+
+        let frame_count = (header_size as usize - toc_pos) / 4;
 
-        let mut frame_offsets = Vec::with_capacity(frame_count as usize);
+        let mut frame_offsets = Vec::with_capacity(frame_count);
 
         for _ in 0..frame_count {
             frame_offsets.push(r.read_le_u32()?);
         }
 
-        let buffer = Vec::<u8>::new();
+        Ok(HnmDecoder {
+            data,
+            version: HnmVersion::Dune,
+            frames_start: header_size as usize,
+            frame_offsets,
+            frame_decoder: FrameDecoder::Dune(Vec::new()),
+            audio: None,
+            undecoded_audio: false,
+            keyframes: Vec::new(),
+            initial_palette: pal.clone(),
+            palette: pal.clone(),
+            framebuffer: Framebuffer::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+            next_frame: 0,
+        })
+    }
+
+    /// Parses an HNM1 or HNM4 file, which has no initial palette.
+    fn new_signed(data: &'a [u8], version: u8, pal: &mut Palette) -> Result<Self, Error> {
+        let header = signed::Header::new(data)?;
+        let frame_offsets = signed::frame_offsets(data, header.frame_count)?;
+
+        let frame_decoder = if version == 4 {
+            // Row pairs are interleaved
+            if !header.height.is_multiple_of(2) {
+                return Err(Error::InvalidData {
+                    resource: RESOURCE.to_owned(),
+                    offset: 10,
+                    reason: "odd frame height",
+                });
+            }
+            FrameDecoder::Hnm4(Hnm4Buffers::new(header.width, header.height))
+        } else {
+            FrameDecoder::Hnm1(Vec::new())
+        };
 
         Ok(HnmDecoder {
             data,
-            header_size,
+            version: HnmVersion::Signed(version),
+            frames_start: signed::HEADER_SIZE,
             frame_offsets,
-            buffer,
+            frame_decoder,
+            audio: None,
+            undecoded_audio: false,
+            keyframes: Vec::new(),
+            initial_palette: pal.clone(),
+            palette: pal.clone(),
+            framebuffer: Framebuffer::new(header.width, header.height),
+            next_frame: 0,
         })
     }
 
+    pub fn version(&self) -> HnmVersion {
+        self.version
+    }
+
+    /// The width of the frames, 320 for Dune's videos and as given by the
+    /// header for signed files.
+    pub fn width(&self) -> u16 {
+        self.framebuffer.w()
+    }
+
+    /// The height of the frames, 200 for Dune's videos.
+    pub fn height(&self) -> u16 {
+        self.framebuffer.h()
+    }
+
     pub fn frame_count(&self) -> usize {
-        self.frame_offsets.len() - 1
+        self.frame_offsets.len().saturating_sub(1)
+    }
+
+    /// The frames that redraw the whole picture and can be seeked to
+    /// directly.
+    pub fn keyframes(&self) -> &[usize] {
+        &self.keyframes
+    }
+
+    /// The index of the frame the iterator returns next.
+    pub fn position(&self) -> usize {
+        self.next_frame
+    }
+
+    /// Moves the playback position so the iterator returns `frame` next.
+    ///
+    /// Decoding restarts at the closest keyframe before `frame`, after
+    /// replaying the palette changes of all earlier frames.
+    pub fn seek(&mut self, frame: usize) -> Result<(), Error> {
+        if frame > self.frame_count() {
+            return Err(frame_out_of_range());
+        }
+
+        let start = self
+            .keyframes
+            .iter()
+            .rev()
+            .copied()
+            .find(|&keyframe| keyframe <= frame)
+            .unwrap_or(0);
+
+        self.palette = self.initial_palette.clone();
+        self.framebuffer.clear();
+        self.frame_decoder.reset();
+        self.next_frame = 0;
+
+        for index in 0..start {
+            for update in self.frame_blocks(index)?.palette_updates {
+                self.palette.apply_palette_update(update)?;
+            }
+        }
+
+        for index in start..frame {
+            let r = self.frame_reader(index)?;
+            self.frame_decoder
+                .decode(r, &mut self.framebuffer, &mut self.palette)?;
+        }
+        self.next_frame = frame;
+
+        Ok(())
+    }
+
+    /// The format of the audio stream, or `None` if the video is silent.
+    ///
+    /// The sound of HNM4 files isn't decoded, so they have no audio stream
+    /// even if [`HnmDecoder::has_undecoded_audio`] is true.
+    pub fn audio_format(&self) -> Option<AudioFormat> {
+        self.audio.as_ref().map(|audio| audio.format)
+    }
+
+    /// Whether the video has sound that isn't decoded, e.g. the "SD" chunks
+    /// of HNM4 files. Their frames yield no audio samples.
+    pub fn has_undecoded_audio(&self) -> bool {
+        self.undecoded_audio
+    }
+
+    /// The audio samples stored in a frame.
+    ///
+    /// Frames without a sound block yield no samples.
+    pub fn audio_samples(&self, frame: usize) -> Result<AudioSamples<'a>, Error> {
+        let sound = self.frame_blocks(frame)?.sound;
+        Ok(self.samples(frame, sound))
+    }
+
+    fn samples(&self, frame: usize, sound: Option<&'a [u8]>) -> AudioSamples<'a> {
+        let (Some(audio), Some(data)) = (&self.audio, sound) else {
+            return AudioSamples::new(&[]);
+        };
+
+        if frame == audio.first_frame {
+            AudioSamples::new(&data[audio.header_size..])
+        } else {
+            AudioSamples::new(data)
+        }
+    }
+
+    /// Returns a reader over the blocks of a frame.
+    fn frame_reader(&self, frame: usize) -> Result<ResourceReader<'a>, Error> {
+        let Some(&frame_offset) = self.frame_offsets.get(frame) else {
+            return Err(frame_out_of_range());
+        };
+        let frame_pos = self.frames_start + frame_offset as usize;
+
+        let mut r = ResourceReader::new(RESOURCE, self.data);
+        r.set_position(frame_pos)?;
+        let frame_size = self.frame_decoder.read_frame_size(&mut r)?;
+        let data_pos = r.position();
+
+        let frame_end = frame_pos + frame_size;
+        if frame_end > self.data.len() {
+            return Err(r.bad_offset(frame_end));
+        }
+        let mut r = ResourceReader::new(RESOURCE, &self.data[..frame_end]);
+        r.set_position(data_pos)?;
+
+        Ok(r)
+    }
+
+    fn frame_blocks(&self, frame: usize) -> Result<FrameBlocks<'a>, Error> {
+        let r = self.frame_reader(frame)?;
+        self.frame_decoder.frame_blocks(r)
     }
 
     pub fn decode_frame(
@@ -47,66 +380,524 @@ impl<'a> HnmDecoder<'a> {
         frame: usize,
         framebuffer: &mut Framebuffer,
         pal: &mut Palette,
-    ) -> std::io::Result<()> {
-        let frame_pos = self.header_size as usize + self.frame_offsets[frame] as usize;
+    ) -> Result<(), Error> {
+        let r = self.frame_reader(frame)?;
+        self.frame_decoder.decode(r, framebuffer, pal)?;
+
+        Ok(())
+    }
+}
+
+/// Finds the blocks of a Dune frame.
+fn find_blocks(mut r: ResourceReader) -> Result<FrameBlocks, Error> {
+    let mut blocks = FrameBlocks::default();
+
+    while !r.remaining().is_empty() {
+        let block_pos = r.position();
+        let block_type = r.read_be_u16()?;
+
+        match block_type {
+            BLOCK_TYPE_SD | BLOCK_TYPE_PL => {
+                let block_size = r.read_le_u16()? as usize;
+                let update = r.remaining();
+                let data = r.read_slice(
+                    block_size
+                        .checked_sub(4)
+                        .ok_or_else(|| r.bad_offset(block_pos))?,
+                )?;
+                if block_type == BLOCK_TYPE_SD {
+                    blocks.sound = Some(data);
+                } else {
+                    blocks.palette_updates.push(update);
+                }
+            }
+            _ if is_block_type(block_type) => {
+                return Err(unknown_block_type(block_pos, block_type));
+            }
+            _ => {
+                r.set_position(block_pos)?;
+                let video = FrameHeader::new(&mut r.read_slice(4)?)?;
+                blocks.is_keyframe = video.is_full_frame();
+                break;
+            }
+        }
+    }
 
-        println!("decode_frame({frame}): frame_pos = {frame_pos:x}");
+    Ok(blocks)
+}
+
+impl ExactSizeIterator for HnmDecoder<'_> {}
 
-        let mut r = Cursor::new(&self.data[frame_pos..]);
-        let frame_size = r.read_le_u16()?;
+impl Iterator for HnmDecoder<'_> {
+    type Item = Result<DecodedFrame, Error>;
 
-        let frame_end = frame_pos + frame_size as usize;
-        let mut r = Cursor::new(&self.data[frame_pos + 2..frame_end]);
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.next_frame;
+        if index >= self.frame_count() {
+            return None;
+        }
 
-        const BLOCK_TYPE_SD: u16 = 0x7364;
-        const BLOCK_TYPE_PL: u16 = 0x706C;
+        let changes = self.frame_reader(index).and_then(|r| {
+            self.frame_decoder
+                .decode(r, &mut self.framebuffer, &mut self.palette)
+        });
+        let changes = match changes {
+            Ok(changes) => changes,
+            Err(err) => {
+                self.next_frame = self.frame_count();
+                return Some(Err(err));
+            }
+        };
+        self.next_frame += 1;
 
-        loop {
-            let block_type = r.read_be_u16()?;
+        Some(Ok(DecodedFrame {
+            index,
+            is_full_frame: changes.is_full_frame,
+            rect: changes.rect,
+            framebuffer: self.framebuffer.clone(),
+            palette: changes.palette_changed.then(|| self.palette.clone()),
+            audio: self.samples(index, changes.sound).collect(),
+        }))
+    }
 
-            match block_type {
-                BLOCK_TYPE_SD => {
-                    let block_size = r.read_le_u16()?;
-                    r.seek_relative(block_size as i64 - 4)?;
-                }
-                BLOCK_TYPE_PL => {
-                    let block_size = r.read_le_u16()?;
-                    let pal_data = r.split().1;
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.frame_count().saturating_sub(self.next_frame);
+        (remaining, Some(remaining))
+    }
+}
 
-                    pal.apply_palette_update(pal_data)?;
+fn frame_out_of_range() -> Error {
+    Error::InvalidData {
+        resource: RESOURCE.to_owned(),
+        offset: 2,
+        reason: "frame index out of range",
+    }
+}
 
-                    r.seek_relative(block_size as i64 - 4)?;
-                }
-                _ => {
-                    r.seek_relative(-2)?;
-                    let frame_header = FrameHeader::new(&mut r)?;
-
-                    if frame_header.is_compressed() {
-                        r.seek_relative(6)?;
-                        let mut w = Cursor::new(&mut self.buffer);
-                        hsq::unhsq(r, &mut w)?;
-                        r = Cursor::new(&self.buffer);
-                    };
-
-                    let (x, y) = if frame_header.is_full_frame() {
-                        (0, 0)
-                    } else {
-                        (r.read_le_u16()? as i16, r.read_le_u16()? as i16)
-                    };
-
-                    let data = r.split().1;
-
-                    blit::Blitter::new(data, framebuffer)
-                        .at(x, y)
-                        .size(frame_header.width(), frame_header.height())
-                        .pal_offset(frame_header.mode())
-                        .draw()?;
-
-                    break;
+/// Block types are two lowercase ASCII letters, anything else starts the
+/// video data.
+fn is_block_type(block_type: u16) -> bool {
+    block_type.to_be_bytes().iter().all(u8::is_ascii_lowercase)
+}
+
+fn unknown_block_type(offset: usize, block_type: u16) -> Error {
+    Error::UnknownBlockType {
+        resource: RESOURCE.to_owned(),
+        offset,
+        block_type,
+    }
+}
+
+fn decode_blocks<'a>(
+    mut r: ResourceReader<'a>,
+    buffer: &mut Vec<u8>,
+    framebuffer: &mut Framebuffer,
+    pal: &mut Palette,
+) -> Result<FrameChanges<'a>, Error> {
+    let mut changes = FrameChanges {
+        rect: Rect::default(),
+        is_full_frame: false,
+        palette_changed: false,
+        sound: None,
+    };
+
+    while !r.remaining().is_empty() {
+        let block_pos = r.position();
+        let block_type = r.read_be_u16()?;
+
+        match block_type {
+            BLOCK_TYPE_SD => {
+                let block_size = r.read_le_u16()? as usize;
+                changes.sound = Some(
+                    r.read_slice(
+                        block_size
+                            .checked_sub(4)
+                            .ok_or_else(|| r.bad_offset(block_pos))?,
+                    )?,
+                );
+            }
+            BLOCK_TYPE_PL => {
+                let block_size = r.read_le_u16()? as usize;
+                let pal_data = r.remaining();
+
+                pal.apply_palette_update(pal_data)?;
+                changes.palette_changed = true;
+
+                r.skip(
+                    block_size
+                        .checked_sub(4)
+                        .ok_or_else(|| r.bad_offset(block_pos))?,
+                )?;
+            }
+            _ if is_block_type(block_type) => {
+                return Err(unknown_block_type(block_pos, block_type));
+            }
+            _ => {
+                r.set_position(block_pos)?;
+                let frame_header = FrameHeader::new(&mut r.read_slice(4)?)?;
+
+                if frame_header.width() == 0 || frame_header.height() == 0 {
+                    return Err(r.invalid_data("empty frame"));
                 }
+
+                let mut frame_data = r;
+                if frame_header.is_compressed() {
+                    frame_data.skip(6)?;
+                    let compressed_pos = frame_data.position();
+                    buffer.clear();
+                    hsq::unhsq(frame_data.remaining(), buffer)
+                        .map_err(|err| Error::from_read_error(err, RESOURCE, compressed_pos))?;
+                    frame_data = ResourceReader::new(RESOURCE, buffer);
+                };
+
+                let (x, y) = if frame_header.is_full_frame() {
+                    (0, 0)
+                } else {
+                    (
+                        frame_data.read_le_u16()? as i16,
+                        frame_data.read_le_u16()? as i16,
+                    )
+                };
+
+                let data = frame_data.remaining();
+
+                blit::Blitter::new(data, framebuffer)
+                    .at(x, y)
+                    .size(frame_header.width(), frame_header.height())
+                    .pal_offset(frame_header.mode())
+                    .draw()
+                    .map_err(|err| Error::from_read_error(err, RESOURCE, frame_data.position()))?;
+
+                let rect = Rect {
+                    x0: x,
+                    y0: y,
+                    x1: x.saturating_add_unsigned(frame_header.width()),
+                    y1: y.saturating_add_unsigned(frame_header.height()),
+                };
+                let screen = Rect {
+                    x0: 0,
+                    y0: 0,
+                    x1: framebuffer.w() as i16,
+                    y1: framebuffer.h() as i16,
+                };
+                changes.rect = rect.clip(&screen);
+                changes.is_full_frame = frame_header.is_full_frame();
+
+                break;
             }
         }
+    }
 
-        Ok(())
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    fn frame(blocks: &[(&[u8; 2], &[u8])], video: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0, 0];
+        for (block_type, data) in blocks {
+            frame.extend_from_slice(*block_type);
+            frame.extend_from_slice(&(data.len() as u16 + 4).to_le_bytes());
+            frame.extend_from_slice(data);
+        }
+        frame.extend_from_slice(video);
+
+        let size = frame.len() as u16;
+        frame[0..2].copy_from_slice(&size.to_le_bytes());
+        frame
+    }
+
+    /// An 8bpp 2x1 frame drawn at the top left corner.
+    fn full_frame(c0: u8, c1: u8) -> [u8; 6] {
+        [0x02, 0x04, 0x01, 0xfe, c0, c1]
+    }
+
+    /// An 8bpp 1x1 frame drawn at (x, 0).
+    fn partial_frame(x: u8, c: u8) -> [u8; 9] {
+        [0x01, 0x00, 0x01, 0xfe, x, 0x00, 0x00, 0x00, c]
+    }
+
+    fn build_hnm(frames: &[Vec<u8>]) -> Vec<u8> {
+        let header_size = 4 + 4 * (frames.len() + 1);
+        let mut data = Vec::new();
+        data.extend_from_slice(&(header_size as u16).to_le_bytes());
+        data.extend_from_slice(&[0xff, 0xff]);
+
+        let mut offset = 0u32;
+        for frame in frames {
+            data.extend_from_slice(&offset.to_le_bytes());
+            offset += frame.len() as u32;
+        }
+        data.extend_from_slice(&offset.to_le_bytes());
+
+        for frame in frames {
+            data.extend_from_slice(frame);
+        }
+        data
+    }
+
+    /// An HNM4 chunk.
+    fn chunk(chunk_type: &[u8; 2], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32 + 8).to_le_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(&[0, 0]);
+        chunk.extend_from_slice(data);
+        chunk
+    }
+
+    /// A signed file with a superchunk holding each of `frames`.
+    fn build_signed(version: u8, width: u16, height: u16, frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = b"HNM".to_vec();
+        data.push(b'0' + version);
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&(frames.len() as u32).to_le_bytes());
+        data.resize(signed::HEADER_SIZE, 0);
+
+        for frame in frames {
+            data.extend_from_slice(&(frame.len() as u32 + 4).to_le_bytes());
+            data.extend_from_slice(frame);
+        }
+        data
+    }
+
+    #[test]
+    fn test_dune_version() {
+        let data = build_hnm(&[
+            frame(
+                &[(b"pl", &[0x10, 0x01, 0x3f, 0x00, 0x00, 0xff, 0xff])],
+                &full_frame(1, 2),
+            ),
+            frame(&[], &partial_frame(1, 3)),
+        ]);
+        assert_eq!(HnmVersion::detect(&data), HnmVersion::Dune);
+
+        let mut pal = Palette::new();
+        let hnm = HnmDecoder::new(&data, &mut pal).unwrap();
+        assert_eq!(hnm.version(), HnmVersion::Dune);
+        assert_eq!(hnm.frame_count(), 2);
+        assert_eq!((hnm.width(), hnm.height()), (320, 200));
+
+        let frames: Vec<DecodedFrame> = hnm.map(Result::unwrap).collect();
+        assert_eq!(
+            frames[0].palette.as_ref().unwrap().get(0x10),
+            Color(0x3f, 0, 0)
+        );
+        assert_eq!(frames[1].framebuffer.get(1, 0), 3);
+    }
+
+    #[test]
+    fn test_hnm1_version() {
+        // An 8bpp 1x1 frame drawn at (5, 220), below Dune's screen
+        let low_frame = [0x01, 0x00, 0x01, 0xfe, 0x05, 0x00, 0xdc, 0x00, 0x07];
+        let data = build_signed(
+            1,
+            320,
+            240,
+            &[
+                frame(
+                    &[(b"pl", &[0x10, 0x01, 0x3f, 0x00, 0x00, 0xff, 0xff])],
+                    &full_frame(1, 2),
+                )[2..]
+                    .to_vec(),
+                frame(&[(b"sd", &[0x80, 0x90])], &low_frame)[2..].to_vec(),
+            ],
+        );
+        assert_eq!(HnmVersion::detect(&data), HnmVersion::Signed(1));
+        assert_eq!(HnmVersion::Signed(1).to_string(), "HNM1");
+
+        let mut pal = Palette::new();
+        let mut hnm = HnmDecoder::new(&data, &mut pal).unwrap();
+        assert_eq!(hnm.version(), HnmVersion::Signed(1));
+        assert_eq!(hnm.frame_count(), 2);
+        assert_eq!((hnm.width(), hnm.height()), (320, 240));
+        assert_eq!(hnm.keyframes(), [0]);
+        assert_eq!(hnm.audio_format().unwrap().channels, 1);
+        assert!(!hnm.has_undecoded_audio());
+
+        let frames: Vec<DecodedFrame> = hnm.by_ref().map(Result::unwrap).collect();
+        assert!(frames[0].is_full_frame);
+        assert_eq!(
+            frames[0].palette.as_ref().unwrap().get(0x10),
+            Color(0x3f, 0, 0)
+        );
+        assert_eq!(frames[0].framebuffer.get(1, 0), 2);
+
+        assert_eq!(frames[1].audio, [0x0000, 0x1000]);
+        assert_eq!(frames[1].framebuffer.get(5, 220), 7);
+        assert_eq!(frames[1].framebuffer.get(0, 0), 1);
+
+        let mut data = data;
+        data.truncate(data.len() - 1);
+        assert!(HnmDecoder::new(&data, &mut pal).is_err());
+    }
+
+    #[test]
+    fn test_hnm4_version() {
+        // A 4x2 keyframe of eight literals, stored with the rows interleaved
+        let mut keyframe = vec![0; 4];
+        keyframe.extend_from_slice(&[0x00, 0x00, 0x40, 0xff, 1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0]);
+        // Stores a pixel pair, skips one, copies one swapped from the
+        // previous frame and ends
+        let interframe = [0x00, 9, 10, 0x20, 0x01, 0x21, 0x01, 0x80, 0x80];
+
+        let data = build_signed(
+            4,
+            4,
+            2,
+            &[
+                [
+                    chunk(b"PL", &[0x10, 0x01, 0x3f, 0x00, 0x00, 0xff, 0xff]),
+                    chunk(b"IZ", &keyframe),
+                ]
+                .concat(),
+                // Unknown chunks are skipped
+                [
+                    chunk(b"SD", &[0x80; 4]),
+                    chunk(b"XX", &[1, 2, 3]),
+                    chunk(b"IU", &interframe),
+                ]
+                .concat(),
+                [chunk(b"XX", &[]), chunk(b"IZ", &keyframe)].concat(),
+            ],
+        );
+        assert_eq!(HnmVersion::detect(&data), HnmVersion::Signed(4));
+        assert_eq!(HnmVersion::Signed(4).to_string(), "HNM4");
+
+        let mut pal = Palette::new();
+        let mut hnm = HnmDecoder::new(&data, &mut pal).unwrap();
+        assert_eq!(hnm.version(), HnmVersion::Signed(4));
+        assert_eq!(hnm.frame_count(), 3);
+        assert_eq!((hnm.width(), hnm.height()), (4, 2));
+        assert_eq!(hnm.keyframes(), [0, 2]);
+        assert_eq!(hnm.audio_format(), None);
+        assert!(hnm.has_undecoded_audio());
+
+        let frames: Vec<DecodedFrame> = hnm.by_ref().map(Result::unwrap).collect();
+        assert!(frames[0].is_full_frame);
+        assert_eq!(
+            frames[0].palette.as_ref().unwrap().get(0x10),
+            Color(0x3f, 0, 0)
+        );
+        assert_eq!(frames[0].framebuffer.pixels(), [1, 3, 5, 7, 2, 4, 6, 8]);
+
+        assert!(!frames[1].is_full_frame);
+        assert!(frames[1].palette.is_none());
+        assert_eq!(frames[1].framebuffer.pixels(), [9, 3, 6, 7, 10, 4, 5, 8]);
+        assert!(frames[1].audio.is_empty());
+        assert_eq!(
+            frames[2].framebuffer.pixels(),
+            frames[0].framebuffer.pixels()
+        );
+
+        hnm.seek(1).unwrap();
+        let frame = hnm.next().unwrap().unwrap();
+        assert_eq!(frame.framebuffer.pixels(), frames[1].framebuffer.pixels());
+
+        let mut hnm5 = data.clone();
+        hnm5[3] = b'5';
+        assert!(matches!(
+            HnmDecoder::new(&hnm5, &mut pal),
+            Err(Error::InvalidData { offset: 3, .. })
+        ));
+
+        let mut data = data;
+        data.truncate(data.len() - 1);
+        assert!(HnmDecoder::new(&data, &mut pal).is_err());
+    }
+
+    #[test]
+    fn test_audio() {
+        let data = build_hnm(&[
+            frame(
+                &[(b"sd", &[0x01, 0x04, 0x00, 0x00, 0xa6, 0x00, 0x80, 0x90])],
+                &full_frame(1, 2),
+            ),
+            frame(&[], &full_frame(1, 2)),
+            frame(&[(b"sd", &[0x70])], &full_frame(1, 2)),
+        ]);
+
+        let mut pal = Palette::new();
+        let mut hnm = HnmDecoder::new(&data, &mut pal).unwrap();
+        assert_eq!(hnm.frame_count(), 3);
+
+        let format = hnm.audio_format().unwrap();
+        assert_eq!(format.sample_rate, 11111);
+        assert_eq!(format.channels, 1);
+
+        let samples: Vec<i16> = hnm.audio_samples(0).unwrap().collect();
+        assert_eq!(samples, [0x0000, 0x1000]);
+        assert_eq!(hnm.audio_samples(1).unwrap().len(), 0);
+        assert_eq!(hnm.audio_samples(2).unwrap().as_bytes(), [0x70]);
+
+        let mut framebuffer = Framebuffer::new(2, 1);
+        hnm.decode_frame(2, &mut framebuffer, &mut pal).unwrap();
+
+        let silent = build_hnm(&[frame(&[], &full_frame(1, 2))]);
+        let hnm = HnmDecoder::new(&silent, &mut pal).unwrap();
+        assert_eq!(hnm.audio_format(), None);
+    }
+
+    #[test]
+    fn test_iterator_and_seek() {
+        let data = build_hnm(&[
+            frame(&[(b"sd", &[0x80, 0x80])], &full_frame(0x11, 0x22)),
+            frame(
+                &[(b"pl", &[0x33, 0x01, 0x3f, 0x00, 0x00, 0xff, 0xff])],
+                &partial_frame(1, 0x33),
+            ),
+            frame(&[], &full_frame(0x44, 0x55)),
+            frame(&[], &partial_frame(0, 0x66)),
+        ]);
+
+        let mut pal = Palette::new();
+        let mut hnm = HnmDecoder::new(&data, &mut pal).unwrap();
+        assert_eq!(hnm.keyframes(), [0, 2]);
+        assert_eq!(hnm.len(), 4);
+
+        let frames: Vec<DecodedFrame> = hnm.by_ref().map(Result::unwrap).collect();
+        assert_eq!(frames.len(), 4);
+        assert!(hnm.next().is_none());
+
+        assert!(frames[0].is_full_frame);
+        assert_eq!(frames[0].audio, [0, 0]);
+        assert!(frames[0].palette.is_none());
+
+        assert!(!frames[1].is_full_frame);
+        assert_eq!(
+            frames[1].rect,
+            Rect {
+                x0: 1,
+                y0: 0,
+                x1: 2,
+                y1: 1
+            }
+        );
+        assert_eq!(frames[1].framebuffer.get(0, 0), 0x11);
+        assert_eq!(frames[1].framebuffer.get(1, 0), 0x33);
+        let red = frames[1].palette.as_ref().unwrap().get(0x33);
+        assert_eq!(red, Color(0x3f, 0, 0));
+
+        assert_eq!(frames[3].framebuffer.get(0, 0), 0x66);
+        assert_eq!(frames[3].framebuffer.get(1, 0), 0x55);
+
+        // Seeking past a keyframe still replays earlier palette changes
+        hnm.seek(3).unwrap();
+        assert_eq!(hnm.position(), 3);
+        let frame = hnm.next().unwrap().unwrap();
+        assert_eq!(frame.index, 3);
+        assert_eq!(frame.framebuffer.pixels(), frames[3].framebuffer.pixels());
+        assert_eq!(hnm.palette.get(0x33), red);
+
+        hnm.seek(1).unwrap();
+        let frame = hnm.next().unwrap().unwrap();
+        assert_eq!(frame.framebuffer.pixels(), frames[1].framebuffer.pixels());
+
+        assert!(hnm.seek(5).is_err());
     }
 }
@@ -0,0 +1,388 @@
+use crate::{Error, Framebuffer, Palette, Rect, hsq};
+
+const RESOURCE: &str = "hnm";
+
+const FLAG_COMPRESSED: u8 = 0x02;
+const FLAG_FULL_FRAME: u8 = 0x04;
+
+/// Frames are stored as 8bpp pixels without a palette offset.
+const MODE_8BPP: u8 = 0xfe;
+
+const MAX_WIDTH: u16 = 511;
+const MAX_HEIGHT: u16 = 255;
+
+/// Encodes HNM videos that [`HnmDecoder`](super::HnmDecoder) and the game
+/// can play back.
+///
+/// The first frame is stored in full, later frames only store the rectangle
+/// that changed since the previous frame. Palette changes are written as
+/// "pl" blocks holding the changed ranges of colors.
+pub struct HnmEncoder {
+    initial_palette: Palette,
+    palette: Palette,
+    framebuffer: Option<Framebuffer>,
+    frames: Vec<Vec<u8>>,
+    compress: bool,
+    keyframe_interval: usize,
+}
+
+impl HnmEncoder {
+    /// Creates an encoder for a video starting with `palette`.
+    pub fn new(palette: &Palette) -> Self {
+        Self {
+            initial_palette: palette.clone(),
+            palette: palette.clone(),
+            framebuffer: None,
+            frames: Vec::new(),
+            compress: true,
+            keyframe_interval: 0,
+        }
+    }
+
+    /// Whether to compress frames with HSQ. Frames are only stored
+    /// compressed when that makes them smaller. Enabled by default.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Stores every `interval`th frame in full so the video can be seeked.
+    /// With the default of 0 only the first frame is a full frame.
+    pub fn keyframe_interval(mut self, interval: usize) -> Self {
+        self.keyframe_interval = interval;
+        self
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Adds a frame showing `framebuffer` with `palette`.
+    pub fn add_frame(&mut self, framebuffer: &Framebuffer, palette: &Palette) -> Result<(), Error> {
+        self.add_frame_with_sound(framebuffer, palette, &[])
+    }
+
+    /// Adds a frame with a sound block of unsigned 8-bit samples.
+    ///
+    /// The sound block of the first frame with sound may start with a VOC
+    /// header giving the sample rate, see
+    /// [`HnmDecoder::audio_format`](super::HnmDecoder::audio_format).
+    pub fn add_frame_with_sound(
+        &mut self,
+        framebuffer: &Framebuffer,
+        palette: &Palette,
+        sound: &[u8],
+    ) -> Result<(), Error> {
+        if framebuffer.w() == 0 || framebuffer.h() == 0 {
+            return Err(invalid_data("empty frame"));
+        }
+        if framebuffer.w() > MAX_WIDTH || framebuffer.h() > MAX_HEIGHT {
+            return Err(invalid_data("frame too large"));
+        }
+
+        let is_keyframe =
+            self.keyframe_interval != 0 && self.frames.len().is_multiple_of(self.keyframe_interval);
+
+        let update = match &self.framebuffer {
+            None => Some((full_rect(framebuffer), true)),
+            Some(previous)
+                if previous.w() != framebuffer.w() || previous.h() != framebuffer.h() =>
+            {
+                return Err(invalid_data("frame size changed"));
+            }
+            Some(_) if is_keyframe => Some((full_rect(framebuffer), true)),
+            Some(previous) => dirty_rect(previous, framebuffer).map(|rect| (rect, false)),
+        };
+
+        let mut frame = vec![0, 0];
+
+        if !sound.is_empty() {
+            write_block(&mut frame, b"sd", sound)?;
+        }
+
+        let palette_update = palette_update(&self.palette, palette);
+        if !palette_update.is_empty() {
+            write_block(&mut frame, b"pl", &palette_update)?;
+        }
+
+        if let Some((rect, is_full_frame)) = update {
+            self.write_video(&mut frame, framebuffer, rect, is_full_frame)?;
+        }
+
+        let frame_size = u16::try_from(frame.len()).map_err(|_| invalid_data("frame too large"))?;
+        frame[0..2].copy_from_slice(&frame_size.to_le_bytes());
+
+        self.frames.push(frame);
+        self.palette = palette.clone();
+        match &mut self.framebuffer {
+            Some(previous) => previous.copy_from(framebuffer),
+            None => self.framebuffer = Some(framebuffer.clone()),
+        }
+
+        Ok(())
+    }
+
+    fn write_video(
+        &self,
+        frame: &mut Vec<u8>,
+        framebuffer: &Framebuffer,
+        rect: Rect,
+        is_full_frame: bool,
+    ) -> Result<(), Error> {
+        let w = (rect.x1 - rect.x0) as u16;
+        let h = (rect.y1 - rect.y0) as u16;
+
+        let mut data = Vec::with_capacity(4 + w as usize * h as usize);
+        if !is_full_frame {
+            data.extend_from_slice(&(rect.x0 as u16).to_le_bytes());
+            data.extend_from_slice(&(rect.y0 as u16).to_le_bytes());
+        }
+        for y in rect.y0..rect.y1 {
+            let row = y as usize * framebuffer.w() as usize;
+            data.extend_from_slice(
+                &framebuffer.pixels()[row + rect.x0 as usize..row + rect.x1 as usize],
+            );
+        }
+
+        let mut flags = if is_full_frame { FLAG_FULL_FRAME } else { 0 };
+        // Data that doesn't compress can be too large for an HSQ header
+        if self.compress
+            && let Ok(compressed) = hsq::compress(&data)
+            && compressed.len() < data.len()
+        {
+            flags |= FLAG_COMPRESSED;
+            data = compressed;
+        }
+
+        frame.extend_from_slice(&[w as u8, flags | (w >> 8) as u8, h as u8, MODE_8BPP]);
+        frame.extend_from_slice(&data);
+
+        Ok(())
+    }
+
+    /// Writes the header and all frames.
+    pub fn finish(self) -> Result<Vec<u8>, Error> {
+        let mut palette = vec![0x00, 0x00];
+        for c in self.initial_palette.as_slice() {
+            palette.extend_from_slice(&[c.0, c.1, c.2]);
+        }
+        palette.extend_from_slice(&[0xff, 0xff]);
+
+        let header_size = 2 + palette.len() + 4 * (self.frames.len() + 1);
+        let header_size =
+            u16::try_from(header_size).map_err(|_| invalid_data("too many frames"))?;
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&header_size.to_le_bytes());
+        data.extend_from_slice(&palette);
+
+        let mut offset = 0u32;
+        for frame in &self.frames {
+            data.extend_from_slice(&offset.to_le_bytes());
+            offset += frame.len() as u32;
+        }
+        data.extend_from_slice(&offset.to_le_bytes());
+
+        for frame in &self.frames {
+            data.extend_from_slice(frame);
+        }
+
+        Ok(data)
+    }
+}
+
+fn invalid_data(reason: &'static str) -> Error {
+    Error::InvalidData {
+        resource: RESOURCE.to_owned(),
+        offset: 0,
+        reason,
+    }
+}
+
+fn write_block(frame: &mut Vec<u8>, block_type: &[u8; 2], data: &[u8]) -> Result<(), Error> {
+    let block_size = u16::try_from(data.len() + 4).map_err(|_| invalid_data("block too large"))?;
+
+    frame.extend_from_slice(block_type);
+    frame.extend_from_slice(&block_size.to_le_bytes());
+    frame.extend_from_slice(data);
+
+    Ok(())
+}
+
+fn full_rect(framebuffer: &Framebuffer) -> Rect {
+    Rect {
+        x0: 0,
+        y0: 0,
+        x1: framebuffer.w() as i16,
+        y1: framebuffer.h() as i16,
+    }
+}
+
+/// The bounding box of the pixels that differ, or `None` if the frames are
+/// identical.
+fn dirty_rect(previous: &Framebuffer, current: &Framebuffer) -> Option<Rect> {
+    let w = current.w() as usize;
+    let mut rect: Option<Rect> = None;
+
+    for (y, (a, b)) in previous
+        .pixels()
+        .chunks_exact(w)
+        .zip(current.pixels().chunks_exact(w))
+        .enumerate()
+    {
+        let Some(x0) = a.iter().zip(b).position(|(a, b)| a != b) else {
+            continue;
+        };
+        let x1 = w - a.iter().zip(b).rev().position(|(a, b)| a != b).unwrap();
+        let (x0, x1, y) = (x0 as i16, x1 as i16, y as i16);
+
+        rect = Some(match rect {
+            None => Rect {
+                x0,
+                y0: y,
+                x1,
+                y1: y + 1,
+            },
+            Some(rect) => Rect {
+                x0: rect.x0.min(x0),
+                y0: rect.y0,
+                x1: rect.x1.max(x1),
+                y1: y + 1,
+            },
+        });
+    }
+
+    rect
+}
+
+/// Encodes the runs of colors that differ between the palettes, in the
+/// format read by [`Palette::apply_palette_update`]. Empty if the palettes
+/// are the same.
+fn palette_update(previous: &Palette, current: &Palette) -> Vec<u8> {
+    let previous = previous.as_slice();
+    let current = current.as_slice();
+
+    let mut update = Vec::new();
+    let mut i = 0;
+    while i < 256 {
+        if previous[i] == current[i] {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < 256 && previous[i] != current[i] {
+            i += 1;
+        }
+
+        // A count of 0 means all 256 colors
+        update.extend_from_slice(&[start as u8, (i - start) as u8]);
+        for c in &current[start..i] {
+            update.extend_from_slice(&[c.0, c.1, c.2]);
+        }
+    }
+
+    if !update.is_empty() {
+        update.extend_from_slice(&[0xff, 0xff]);
+    }
+
+    update
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, hnm::HnmDecoder};
+
+    fn noise(framebuffer: &mut Framebuffer, seed: u32) {
+        let mut seed = seed;
+        for y in 0..framebuffer.h() {
+            for x in 0..framebuffer.w() {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                framebuffer.set(x, y, (seed >> 24) as u8);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut pal = Palette::new();
+        pal.set(1, Color(0x3f, 0x20, 0x00));
+
+        let mut framebuffer = Framebuffer::new(320, 200);
+        let mut frames = Vec::new();
+        let mut palettes = Vec::new();
+
+        noise(&mut framebuffer, 1);
+        frames.push(framebuffer.clone());
+        palettes.push(pal.clone());
+
+        // Unchanged frame
+        frames.push(framebuffer.clone());
+        palettes.push(pal.clone());
+
+        for y in 50..60 {
+            for x in 100..180 {
+                framebuffer.set(x, y, 7);
+            }
+        }
+        frames.push(framebuffer.clone());
+        pal.set(0, Color(1, 2, 3));
+        pal.set(200, Color(4, 5, 6));
+        pal.set(201, Color(7, 8, 9));
+        palettes.push(pal.clone());
+
+        framebuffer.set(319, 199, 0);
+        frames.push(framebuffer.clone());
+        palettes.push(pal.clone());
+
+        let mut encoder = HnmEncoder::new(&palettes[0]).keyframe_interval(3);
+        for (i, (framebuffer, palette)) in frames.iter().zip(&palettes).enumerate() {
+            let sound = if i == 1 { &[0x80, 0x90][..] } else { &[] };
+            encoder
+                .add_frame_with_sound(framebuffer, palette, sound)
+                .unwrap();
+        }
+        assert_eq!(encoder.frame_count(), 4);
+        let data = encoder.finish().unwrap();
+
+        let mut initial = Palette::new();
+        let hnm = HnmDecoder::new(&data, &mut initial).unwrap();
+        assert_eq!(initial.get(1), Color(0x3f, 0x20, 0x00));
+        assert_eq!(hnm.frame_count(), 4);
+        assert_eq!(hnm.keyframes(), [0, 3]);
+        assert_eq!(hnm.audio_samples(1).unwrap().as_bytes(), [0x80, 0x90]);
+
+        let decoded: Vec<_> = hnm.map(Result::unwrap).collect();
+        for (i, frame) in decoded.iter().enumerate() {
+            assert_eq!(frame.framebuffer.pixels(), frames[i].pixels(), "frame {i}");
+        }
+
+        assert!(decoded[0].is_full_frame);
+        assert!(decoded[1].rect.is_empty());
+        assert_eq!(
+            decoded[2].rect,
+            Rect {
+                x0: 100,
+                y0: 50,
+                x1: 180,
+                y1: 60
+            }
+        );
+        let palette = decoded[2].palette.as_ref().unwrap();
+        assert_eq!(palette.as_slice(), palettes[2].as_slice());
+        assert!(decoded[3].is_full_frame);
+        assert!(decoded[3].palette.is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let pal = Palette::new();
+        let mut encoder = HnmEncoder::new(&pal);
+
+        assert!(encoder.add_frame(&Framebuffer::new(512, 1), &pal).is_err());
+        encoder.add_frame(&Framebuffer::new(2, 2), &pal).unwrap();
+        assert!(encoder.add_frame(&Framebuffer::new(3, 2), &pal).is_err());
+        assert_eq!(encoder.frame_count(), 1);
+    }
+}
@@ -1,4 +1,12 @@
+mod audio;
 mod frame_header;
+mod hnm4;
 mod hnm_decoder;
+mod hnm_encoder;
+mod signed;
+mod version;
 
-pub use hnm_decoder::HnmDecoder;
+pub use audio::{AudioFormat, AudioSamples, DEFAULT_SAMPLE_RATE};
+pub use hnm_decoder::{DecodedFrame, HnmDecoder};
+pub use hnm_encoder::HnmEncoder;
+pub use version::HnmVersion;
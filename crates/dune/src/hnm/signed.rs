@@ -0,0 +1,81 @@
+//! The layout shared by signed HNM files, see [`HnmVersion::Signed`].
+//!
+//! Files start with a 64-byte header holding the "HNMx" signature, the
+//! frame size and the frame count, followed by one superchunk per frame.
+//! Each superchunk starts with its size in the low 24 bits of a 32-bit
+//! word, which lets frames be larger than the 64 KiB of Dune's.
+//!
+//! [`HnmVersion::Signed`]: super::HnmVersion::Signed
+
+use crate::{Error, resource_reader::ResourceReader};
+
+const RESOURCE: &str = "hnm";
+
+pub(super) const HEADER_SIZE: usize = 64;
+
+pub(super) struct Header {
+    pub width: u16,
+    pub height: u16,
+    pub frame_count: usize,
+}
+
+impl Header {
+    pub fn new(data: &[u8]) -> Result<Self, Error> {
+        let mut r = ResourceReader::new(RESOURCE, data);
+
+        // Signature, flags and bits per pixel
+        r.skip(8)?;
+        let width = r.read_le_u16()?;
+        let height = r.read_le_u16()?;
+        // File size
+        r.skip(4)?;
+        let frame_count = r.read_le_u32()? as usize;
+
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidData {
+                resource: RESOURCE.to_owned(),
+                offset: 8,
+                reason: "invalid frame size",
+            });
+        }
+        if data.len() < HEADER_SIZE {
+            return Err(Error::Truncated {
+                resource: RESOURCE.to_owned(),
+                offset: data.len(),
+            });
+        }
+
+        Ok(Header {
+            width,
+            height,
+            frame_count,
+        })
+    }
+}
+
+/// Reads the size of a superchunk or chunk, which includes the size itself.
+pub(super) fn read_size(r: &mut ResourceReader) -> Result<usize, Error> {
+    Ok((r.read_le_u32()? & 0xff_ffff) as usize)
+}
+
+/// Finds the superchunk of every frame. Returns their offsets from the end
+/// of the header, followed by the end of the last one.
+pub(super) fn frame_offsets(data: &[u8], frame_count: usize) -> Result<Vec<u32>, Error> {
+    let mut r = ResourceReader::new(RESOURCE, data);
+    r.set_position(HEADER_SIZE)?;
+
+    let mut frame_offsets = Vec::with_capacity(frame_count.min(data.len() / 4) + 1);
+    frame_offsets.push(0);
+
+    for _ in 0..frame_count {
+        let frame_pos = r.position();
+        let frame_size = read_size(&mut r)?;
+        if frame_size < 4 {
+            return Err(r.bad_offset(frame_pos));
+        }
+        r.set_position(frame_pos + frame_size)?;
+        frame_offsets.push((r.position() - HEADER_SIZE) as u32);
+    }
+
+    Ok(frame_offsets)
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+
+/// The layout of an HNM file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HnmVersion {
+    /// The layout used by Dune, which has no signature and starts with the
+    /// header size.
+    Dune,
+    /// Files starting with an "HNM" signature followed by a version digit,
+    /// e.g. "HNM4", as used by later Cryo titles. [`HnmDecoder`] decodes
+    /// HNM1 files, which hold Dune's frames in larger superchunks, and the
+    /// video of HNM4 files. Other versions are rejected.
+    ///
+    /// [`HnmDecoder`]: super::HnmDecoder
+    Signed(u8),
+}
+
+impl HnmVersion {
+    /// Detects the version from the start of the file.
+    ///
+    /// A Dune header starting with the same bytes would need a header size
+    /// of over 20000 bytes, far more than any of the game's videos use.
+    pub fn detect(data: &[u8]) -> HnmVersion {
+        match data {
+            [b'H', b'N', b'M', version, ..] if version.is_ascii_digit() => {
+                HnmVersion::Signed(version - b'0')
+            }
+            _ => HnmVersion::Dune,
+        }
+    }
+}
+
+impl fmt::Display for HnmVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HnmVersion::Dune => write!(f, "HNM (Dune)"),
+            HnmVersion::Signed(version) => write!(f, "HNM{version}"),
+        }
+    }
+}
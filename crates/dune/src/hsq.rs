@@ -1,4 +1,4 @@
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Cursor, Error, ErrorKind, Read};
 
 use bytes_ext::ReadBytesExt;
 
@@ -8,6 +8,17 @@ pub struct Header {
 }
 
 impl Header {
+    pub fn new(uncompressed_size: u32, compressed_size: u16) -> Self {
+        let [u0, u1, u2, _] = uncompressed_size.to_le_bytes();
+        let [c0, c1] = compressed_size.to_le_bytes();
+
+        let mut header = [u0, u1, u2, c0, c1, 0];
+        let sum = header.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        header[5] = 0xABu8.wrapping_sub(sum);
+
+        Header { header }
+    }
+
     pub fn from_reader<R: Read>(r: &mut R) -> std::io::Result<Self> {
         let mut header = [0u8; 6];
         r.read_exact(&mut header)?;
@@ -40,67 +51,550 @@ struct Reader<R: Read> {
     r: R,
 }
 
+enum Token {
+    Literal(u8),
+    Copy { offset: usize, len: usize },
+    End,
+}
+
 impl<R: Read> Reader<R> {
-    pub fn read_bit(&mut self) -> bool {
+    fn new(r: R) -> Self {
+        Reader { queue: 0, r }
+    }
+
+    pub fn read_bit(&mut self) -> std::io::Result<bool> {
         let mut queue = self.queue;
         let mut bit = (queue & 1) == 1;
         queue >>= 1;
         if queue == 0 {
-            queue = self.read_le_u16();
+            queue = self.read_le_u16()?;
             bit = (queue & 1) == 1;
             queue = 0x8000 | (queue >> 1);
         }
         self.queue = queue;
-        bit
+        Ok(bit)
+    }
+    pub fn read_u8(&mut self) -> std::io::Result<u8> {
+        self.r.read_u8()
     }
-    pub fn read_u8(&mut self) -> u8 {
-        self.r.read_u8().unwrap()
+    pub fn read_le_u16(&mut self) -> std::io::Result<u16> {
+        self.r.read_le_u16()
     }
-    pub fn read_le_u16(&mut self) -> u16 {
-        self.r.read_le_u16().unwrap()
+
+    fn read_token(&mut self) -> std::io::Result<Token> {
+        if self.read_bit()? {
+            return Ok(Token::Literal(self.read_u8()?));
+        }
+
+        let count;
+        let offset;
+        if self.read_bit()? {
+            let word = self.read_le_u16()?;
+            offset = LONG_MAX_OFFSET - (word >> 3) as usize;
+            count = match word & 7 {
+                0 => self.read_u8()? as usize,
+                count => count as usize,
+            };
+            if count == 0 {
+                return Ok(Token::End);
+            }
+        } else {
+            let b0 = self.read_bit()? as usize;
+            let b1 = self.read_bit()? as usize;
+
+            count = 2 * b0 + b1;
+            offset = SHORT_MAX_OFFSET - self.read_u8()? as usize;
+        }
+
+        Ok(Token::Copy {
+            offset,
+            len: count + 2,
+        })
     }
 }
 
-pub fn unhsq<R: Read, W: Read + Write + Seek>(r: R, w: &mut W) -> std::io::Result<()> {
-    let mut r = Reader { queue: 0, r };
-    let mut w_ofs: u16 = 0;
+fn bad_back_reference() -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        "hsq back-reference before start of data",
+    )
+}
+
+/// Decompresses an HSQ stream, without its header, appending the result to
+/// `out`.
+///
+/// Back-references can only reach bytes produced by this call.
+pub fn unhsq<R: Read>(r: R, out: &mut Vec<u8>) -> std::io::Result<()> {
+    let mut r = Reader::new(r);
+    let start = out.len();
 
     loop {
-        if r.read_bit() {
-            w.seek(SeekFrom::Start(w_ofs as u64))?;
-            w.write_all(&[r.read_u8()])?;
-            w_ofs += 1;
+        match r.read_token()? {
+            Token::Literal(b) => out.push(b),
+            Token::Copy { offset, len } => {
+                if offset > out.len() - start {
+                    return Err(bad_back_reference());
+                }
+
+                let src = out.len() - offset;
+                if offset >= len {
+                    out.extend_from_within(src..src + len);
+                } else {
+                    for i in src..src + len {
+                        out.push(out[i]);
+                    }
+                }
+            }
+            Token::End => return Ok(()),
+        }
+    }
+}
+
+const WINDOW_SIZE: usize = LONG_MAX_OFFSET;
+
+/// Streaming HSQ decompressor.
+///
+/// Reads an HSQ stream, without its header, from `R` as the output is
+/// consumed and only keeps the last 8 KiB of output around for
+/// back-references.
+pub struct Decoder<R: Read> {
+    r: Reader<R>,
+    window: Box<[u8; WINDOW_SIZE]>,
+    pos: usize,
+    copy_offset: usize,
+    copy_len: usize,
+    done: bool,
+    error: Option<Error>,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(r: R) -> Self {
+        Decoder {
+            r: Reader::new(r),
+            window: Box::new([0; WINDOW_SIZE]),
+            pos: 0,
+            copy_offset: 0,
+            copy_len: 0,
+            done: false,
+            error: None,
+        }
+    }
+
+    /// Total number of bytes decoded so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn into_inner(self) -> R {
+        self.r.r
+    }
+
+    fn push(&mut self, b: u8) {
+        self.window[self.pos % WINDOW_SIZE] = b;
+        self.pos += 1;
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+
+        let mut n = 0;
+        while n < buf.len() {
+            if self.copy_len == 0 {
+                if self.done {
+                    break;
+                }
+
+                let token = self.r.read_token().and_then(|token| match token {
+                    Token::Copy { offset, .. } if offset > self.pos => Err(bad_back_reference()),
+                    token => Ok(token),
+                });
+
+                match token {
+                    Ok(Token::Literal(b)) => {
+                        self.push(b);
+                        buf[n] = b;
+                        n += 1;
+                    }
+                    Ok(Token::Copy { offset, len }) => {
+                        self.copy_offset = offset;
+                        self.copy_len = len;
+                    }
+                    Ok(Token::End) => self.done = true,
+                    // Hand out what was decoded before reporting the error.
+                    Err(err) if n > 0 => {
+                        self.error = Some(err);
+                        break;
+                    }
+                    Err(err) => return Err(err),
+                }
+                continue;
+            }
+
+            let b = self.window[(self.pos - self.copy_offset) % WINDOW_SIZE];
+            self.push(b);
+            buf[n] = b;
+            n += 1;
+            self.copy_len -= 1;
+        }
+
+        Ok(n)
+    }
+}
+
+/// Decompresses an HSQ resource, including its 6-byte header.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, crate::Error> {
+    const RESOURCE: &str = "hsq data";
+
+    let header = Header::from_reader(&mut Cursor::new(data))
+        .map_err(|err| crate::Error::from_read_error(err, RESOURCE, 0))?;
+
+    if !header.is_compressed() {
+        return Err(crate::Error::BadChecksum {
+            resource: RESOURCE.to_owned(),
+            checksum: header.checksum(),
+        });
+    }
+
+    if header.compressed_size() as usize != data.len() {
+        return Err(crate::Error::SizeMismatch {
+            resource: RESOURCE.to_owned(),
+            expected: header.compressed_size() as usize,
+            actual: data.len(),
+        });
+    }
+
+    let mut unpacked_data = Vec::with_capacity(header.uncompressed_size() as usize);
+    unhsq(&data[6..], &mut unpacked_data).map_err(|err| match err.kind() {
+        ErrorKind::InvalidData => crate::Error::InvalidData {
+            resource: RESOURCE.to_owned(),
+            offset: 0,
+            reason: "back-reference before start of data",
+        },
+        _ => crate::Error::from_read_error(err, RESOURCE, data.len()),
+    })?;
+
+    if unpacked_data.len() != header.uncompressed_size() as usize {
+        return Err(crate::Error::SizeMismatch {
+            resource: RESOURCE.to_owned(),
+            expected: header.uncompressed_size() as usize,
+            actual: unpacked_data.len(),
+        });
+    }
+
+    Ok(unpacked_data)
+}
+
+const SHORT_MAX_OFFSET: usize = 256;
+const SHORT_MAX_LEN: usize = 5;
+const LONG_MAX_OFFSET: usize = 8192;
+const LONG_MAX_LEN: usize = 257;
+const MAX_CHAIN_LENGTH: usize = 1024;
+
+struct Writer {
+    queue: u16,
+    queue_len: u8,
+    queue_pos: usize,
+    w: Vec<u8>,
+}
+
+impl Writer {
+    // The decoder fetches a new control word the moment it runs out of bits,
+    // so reserve room for it in the stream when the first bit is queued.
+    pub fn write_bit(&mut self, bit: bool) {
+        if self.queue_len == 0 {
+            self.queue_pos = self.w.len();
+            self.w.extend_from_slice(&[0, 0]);
+        }
+        self.queue |= (bit as u16) << self.queue_len;
+        self.queue_len += 1;
+        if self.queue_len == 16 {
+            self.flush_queue();
+        }
+    }
+
+    pub fn write_u8(&mut self, v: u8) {
+        self.w.push(v);
+    }
+
+    pub fn write_le_u16(&mut self, v: u16) {
+        self.w.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn flush_queue(&mut self) {
+        if self.queue_len != 0 {
+            self.w[self.queue_pos..self.queue_pos + 2].copy_from_slice(&self.queue.to_le_bytes());
+        }
+        self.queue = 0;
+        self.queue_len = 0;
+    }
+
+    pub fn literal(&mut self, b: u8) {
+        self.write_bit(true);
+        self.write_u8(b);
+    }
+
+    pub fn short_copy(&mut self, offset: usize, len: usize) {
+        let count = len - 2;
+        self.write_bit(false);
+        self.write_bit(false);
+        self.write_bit(count & 2 != 0);
+        self.write_bit(count & 1 != 0);
+        self.write_u8((SHORT_MAX_OFFSET - offset) as u8);
+    }
+
+    pub fn long_copy(&mut self, offset: usize, len: usize) {
+        let count = len - 2;
+        let word = ((LONG_MAX_OFFSET - offset) as u16) << 3;
+        self.write_bit(false);
+        self.write_bit(true);
+        if count <= 7 {
+            self.write_le_u16(word | count as u16);
         } else {
-            let mut count: u16;
-            let offset: u16;
-            if r.read_bit() {
-                let word = r.read_le_u16();
-                count = word & 7;
-                offset = 8192 - (word >> 3);
-                if count == 0 {
-                    count = r.read_u8() as u16;
+            self.write_le_u16(word);
+            self.write_u8(count as u8);
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.write_bit(false);
+        self.write_bit(true);
+        self.write_le_u16(0);
+        self.write_u8(0);
+        self.flush_queue();
+        self.w
+    }
+}
+
+/// Compresses `data` into an HSQ resource, including the 6-byte header.
+pub fn compress(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    if data.len() > 0xFF_FFFF {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "data too large for hsq compression",
+        ));
+    }
+
+    let mut w = Writer {
+        queue: 0,
+        queue_len: 0,
+        queue_pos: 0,
+        w: vec![0; 6],
+    };
+
+    let hash = |pos: usize| ((data[pos] as usize) << 8) | data[pos + 1] as usize;
+
+    let mut head = vec![usize::MAX; 0x10000];
+    let mut prev = vec![usize::MAX; data.len()];
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let max_len = LONG_MAX_LEN.min(data.len() - pos);
+
+        let mut best = (0, 0);
+        let mut best_short = (0, 0);
+
+        if max_len >= 2 {
+            let mut candidate = head[hash(pos)];
+            let mut chain_length = 0;
+
+            while candidate != usize::MAX && chain_length < MAX_CHAIN_LENGTH {
+                let offset = pos - candidate;
+                if offset > LONG_MAX_OFFSET {
+                    break;
+                }
+
+                let len = (0..max_len)
+                    .take_while(|&i| data[candidate + i] == data[pos + i])
+                    .count();
+
+                if len > best.1 {
+                    best = (offset, len);
+                }
+                if offset <= SHORT_MAX_OFFSET && len.min(SHORT_MAX_LEN) > best_short.1 {
+                    best_short = (offset, len.min(SHORT_MAX_LEN));
                 }
-                if count == 0 {
+                if len == max_len {
                     break;
                 }
-            } else {
-                let b0 = r.read_bit() as u16;
-                let b1 = r.read_bit() as u16;
 
-                count = 2 * b0 + b1;
-                offset = 256 - (r.read_u8() as u16);
+                candidate = prev[candidate];
+                chain_length += 1;
             }
+        }
+
+        let (offset, len) = best;
+        let (short_offset, short_len) = best_short;
 
-            for _ in 0..count + 2 {
-                w.seek(SeekFrom::Start((w_ofs - offset) as u64))?;
-                let b = w.read_u8()?;
+        let step = if short_len >= 2 && short_len >= len.min(SHORT_MAX_LEN + 1) {
+            w.short_copy(short_offset, short_len);
+            short_len
+        } else if len >= 3 {
+            w.long_copy(offset, len);
+            len
+        } else {
+            w.literal(data[pos]);
+            1
+        };
 
-                w.seek(SeekFrom::Start(w_ofs as u64))?;
-                w.write_all(&[b])?;
-                w_ofs += 1;
+        let end = (pos + step).min(data.len() - 1);
+        for (p, link) in prev.iter_mut().enumerate().take(end).skip(pos) {
+            let h = hash(p);
+            *link = head[h];
+            head[h] = p;
+        }
+        pos += step;
+    }
+
+    let mut output = w.finish();
+
+    let compressed_size: u16 = output.len().try_into().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "compressed data too large for hsq header",
+        )
+    })?;
+
+    let header = Header::new(data.len() as u32, compressed_size);
+    output[0..6].copy_from_slice(&header.header);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dat_file::DatFile, test_assets};
+
+    fn round_trip(data: &[u8]) {
+        let compressed = compress(data).unwrap();
+
+        let header = Header::from_reader(&mut Cursor::new(&compressed)).unwrap();
+        assert!(header.is_compressed());
+        assert_eq!(header.compressed_size() as usize, compressed.len());
+        assert_eq!(header.uncompressed_size() as usize, data.len());
+
+        let mut unpacked = Vec::new();
+        unhsq(&compressed[6..], &mut unpacked).unwrap();
+        assert_eq!(unpacked, data);
+
+        // Streaming with reads that don't line up with copies
+        let mut decoder = Decoder::new(&compressed[6..]);
+        let mut streamed = Vec::new();
+        let mut buf = [0; 7];
+        loop {
+            let n = decoder.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
             }
+            streamed.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(streamed, data);
+    }
+
+    #[test]
+    fn test_header_checksum() {
+        let header = Header::new(0x012345, 0x6789);
+        assert!(header.is_compressed());
+        assert_eq!(header.uncompressed_size(), 0x012345);
+        assert_eq!(header.compressed_size(), 0x6789);
+    }
+
+    #[test]
+    fn test_round_trip_small() {
+        round_trip(&[]);
+        round_trip(&[0x42]);
+        round_trip(&[0x42, 0x42]);
+        round_trip(b"abababababab");
+    }
+
+    #[test]
+    fn test_round_trip_short_and_long_copies() {
+        let mut data = Vec::new();
+        for i in 0..2000u32 {
+            data.extend_from_slice(&(i % 37).to_le_bytes());
+            data.extend_from_slice(&(i.wrapping_mul(2654435761) >> 24).to_le_bytes());
         }
+        data.extend(std::iter::repeat_n(0xff, 1000));
+        data.extend_from_within(100..5000);
+        round_trip(&data);
     }
 
-    Ok(())
+    #[test]
+    fn test_decompress_errors() {
+        let mut compressed = compress(b"abcabcabcabc").unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), b"abcabcabcabc");
+
+        assert!(matches!(
+            decompress(&compressed[..4]),
+            Err(crate::Error::Truncated { .. })
+        ));
+
+        let len = compressed.len();
+        assert!(matches!(
+            decompress(&compressed[..len - 1]),
+            Err(crate::Error::SizeMismatch { .. })
+        ));
+
+        compressed[5] ^= 1;
+        assert!(matches!(
+            decompress(&compressed),
+            Err(crate::Error::BadChecksum { .. })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_stream() {
+        let data: Vec<u8> = (0..3000u32).map(|i| (i % 13 + i / 700) as u8).collect();
+        let compressed = compress(&data).unwrap();
+        let stream = &compressed[6..compressed.len() - 3];
+
+        let mut unpacked = Vec::new();
+        let err = unhsq(stream, &mut unpacked).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let mut streamed = Vec::new();
+        let err = Decoder::new(stream).read_to_end(&mut streamed).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(streamed, unpacked);
+
+        // Short copy from 256 bytes back with nothing decoded yet
+        let stream = [0x00, 0x00, 0x00];
+        let err = unhsq(&stream[..], &mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let err = Decoder::new(&stream[..]).read(&mut [0; 4]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_round_trip_assets() {
+        let is_compressed = |data: &[u8]| {
+            Header::from_reader(&mut Cursor::new(data)).is_ok_and(|header| {
+                header.is_compressed() && header.compressed_size() as usize == data.len()
+            })
+        };
+
+        for name in test_assets::names() {
+            let Some(data) = test_assets::read_raw(&name) else {
+                continue;
+            };
+            if is_compressed(&data) {
+                round_trip(&decompress(&data).unwrap());
+            }
+        }
+
+        let Some(data) = test_assets::read_raw("DUNE.DAT") else {
+            return;
+        };
+        let mut dat_file = DatFile::from_slice(&data).unwrap();
+        let names: Vec<String> = dat_file.entries.iter().map(|e| e.name.clone()).collect();
+        for name in names {
+            if is_compressed(dat_file.raw_slice(&name).unwrap()) {
+                round_trip(&dat_file.read(&name).unwrap());
+            }
+        }
+    }
 }
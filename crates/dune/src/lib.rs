@@ -1,10 +1,10 @@
-#![feature(cursor_split)]
 #![feature(random)]
 #![feature(strict_overflow_ops)]
 #![allow(clippy::identity_op)]
 
 pub mod attack;
 mod color;
+mod error;
 mod font;
 mod framebuffer;
 mod globe_renderer;
@@ -13,28 +13,43 @@ mod index_map;
 mod intro_1;
 mod lipsync;
 mod palette;
+mod palette_fader;
+mod palette_format;
 mod point;
 mod rect;
+mod resource_kind;
+mod resource_reader;
+mod rgba_image;
 mod room_renderer;
+mod sky;
 mod sprite;
 mod sprite_blitter;
 mod sprite_sheet;
+#[cfg(test)]
+mod test_assets;
 
 pub mod blit;
 pub mod dat_file;
 pub mod hnm;
 pub mod hsq;
+pub mod scaler;
 
 pub use color::Color;
+pub use error::Error;
 pub use font::{Font, TextAlign, TextContext, TextSize, TextStyle, draw_text};
 pub use framebuffer::Framebuffer;
 pub use globe_renderer::GlobeRenderer;
 pub use index_map::IndexMap;
 pub use lipsync::Lipsync;
 pub use palette::Palette;
+pub use palette_fader::{CycleRange, PaletteCycler, PaletteFader, transition_palette};
+pub use palette_format::PaletteFormat;
 pub use point::Point;
 pub use rect::Rect;
-pub use room_renderer::{DrawOptions, Room, RoomRenderer, RoomSheet};
+pub use resource_kind::ResourceKind;
+pub use rgba_image::{RgbaImage, RgbaOptions};
+pub use room_renderer::{DrawOptions, Room, RoomCharacter, RoomRenderer, RoomSheet, room};
+pub use sky::Sky;
 pub use sprite::Sprite;
 pub use sprite_blitter::SpriteBlitter;
 pub use sprite_sheet::SpriteSheet;
@@ -1,8 +1,4 @@
-use std::io::Cursor;
-
-use bytes_ext::ReadBytesExt;
-
-use crate::{Framebuffer, SpriteSheet, draw_sprite};
+use crate::{Error, Framebuffer, SpriteSheet, draw_sprite, resource_reader::ResourceReader};
 
 #[derive(Debug)]
 pub struct Lipsync {
@@ -29,71 +25,72 @@ pub struct Image {
 }
 
 impl Lipsync {
-    pub fn from_bytes(bytes: &[u8]) -> Self {
-        let mut r = Cursor::new(bytes);
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut r = ResourceReader::new("lipsync", bytes);
 
-        let _unk0 = r.read_le_u16().unwrap();
-        let _size = r.read_le_u16().unwrap();
+        let _unk0 = r.read_le_u16()?;
+        let _size = r.read_le_u16()?;
 
-        let x0 = r.read_le_u16().unwrap() as i16;
-        let y0 = r.read_le_u16().unwrap() as i16;
-        let x1 = r.read_le_u16().unwrap() as i16;
-        let y1 = r.read_le_u16().unwrap() as i16;
+        let x0 = r.read_le_u16()? as i16;
+        let y0 = r.read_le_u16()? as i16;
+        let x1 = r.read_le_u16()? as i16;
+        let y1 = r.read_le_u16()? as i16;
 
         let rect = (x0, y0, x1, y1);
 
-        let image_groups_len = r.read_le_u16().unwrap();
+        let image_groups_len = r.read_le_u16()?;
 
         let image_groups_toc = r.position();
-        let image_groups_offset_0 = r.read_le_u16().unwrap();
+        let image_groups_offset_0 = r.read_le_u16()?;
         let image_groups_entry_count = image_groups_offset_0 as usize / 2;
 
         let mut image_groups = Vec::with_capacity(image_groups_entry_count);
 
         for n in 0..image_groups_entry_count {
-            r.set_position(image_groups_toc + 2 * n as u64);
-            let begin = r.read_le_u16().unwrap() as u64;
-            r.set_position(image_groups_toc + begin);
+            r.set_position(image_groups_toc + 2 * n)?;
+            let begin = r.read_le_u16()? as usize;
+            r.set_position(image_groups_toc + begin)?;
 
             let mut images = Vec::new();
             loop {
-                let id = r.read_u8().unwrap();
+                let id = r.read_u8()?;
                 if id == 0 {
                     break;
                 }
-                let x = r.read_u8().unwrap();
-                let y = r.read_u8().unwrap();
+                let x = r.read_u8()?;
+                let y = r.read_u8()?;
 
                 images.push(Image { id, x, y });
             }
             image_groups.push(images);
         }
 
-        let animations_toc = image_groups_toc + image_groups_len as u64 - 2;
-        r.set_position(animations_toc);
+        let animations_toc = image_groups_toc + image_groups_len as usize - 2;
+        r.set_position(animations_toc)?;
 
-        let animations_offset_0: u16 = r.read_le_u16().unwrap();
+        let animations_offset_0: u16 = r.read_le_u16()?;
         let animations_entry_count = animations_offset_0 as usize / 2;
 
         let mut animations = Vec::with_capacity(animations_entry_count);
         for n in 0..animations_entry_count {
-            r.set_position(animations_toc + 2 * n as u64);
-            let begin = r.read_le_u16().unwrap() as u64;
-            r.set_position(animations_toc + begin);
+            r.set_position(animations_toc + 2 * n)?;
+            let begin = r.read_le_u16()? as usize;
+            r.set_position(animations_toc + begin)?;
 
             let mut animation = Animation::default();
             let mut frame = Frame::default();
 
             loop {
-                let image_group_idx = r.read_u8().unwrap();
+                let image_group_idx = r.read_u8()?;
                 if image_group_idx == 0 {
                     animation.frames.push(frame);
                     frame = Frame::default();
                     continue;
                 } else if image_group_idx == 0xFF {
                     break;
+                } else if image_group_idx == 1 {
+                    return Err(r.invalid_data("invalid image group index"));
                 }
-                assert!(image_group_idx != 1);
                 frame.image_groups.push(image_group_idx - 2);
             }
 
@@ -101,11 +98,11 @@ impl Lipsync {
             animations.push(animation);
         }
 
-        Self {
+        Ok(Self {
             rect,
             image_groups,
             animations,
-        }
+        })
     }
 
     pub fn animation_count(&self) -> usize {
@@ -113,7 +110,9 @@ impl Lipsync {
     }
 
     pub fn animation_frame_count(&self, animation: usize) -> usize {
-        self.animations[animation].frames.len()
+        self.animations
+            .get(animation)
+            .map_or(0, |animation| animation.frames.len())
     }
 
     pub fn draw_image_group(
@@ -121,10 +120,12 @@ impl Lipsync {
         framebuffer: &mut Framebuffer,
         sprite_sheet: &SpriteSheet,
         image_group: usize,
-    ) {
+    ) -> std::io::Result<()> {
         println!("\tDrawing image group {image_group:#04x}");
 
-        let image_group = &self.image_groups[image_group];
+        let Some(image_group) = self.image_groups.get(image_group) else {
+            return Ok(());
+        };
 
         for image in image_group {
             println!(
@@ -137,10 +138,11 @@ impl Lipsync {
                     image.x as i16, /* + lipsync.rect.0 as usize */
                     image.y as i16, /* + lipsync.rect.1 as usize */
                     framebuffer,
-                )
-                .unwrap();
+                )?;
             }
         }
+
+        Ok(())
     }
 
     pub fn draw_animation_frame(
@@ -149,14 +151,21 @@ impl Lipsync {
         sprite_sheet: &SpriteSheet,
         animation: usize,
         frame: usize,
-    ) {
-        let animation = &self.animations[animation];
-        let frame = &animation.frames[frame];
+    ) -> std::io::Result<()> {
+        let Some(frame) = self
+            .animations
+            .get(animation)
+            .and_then(|animation| animation.frames.get(frame))
+        else {
+            return Ok(());
+        };
 
         for &image_group_idx in &frame.image_groups {
             let image_group = image_group_idx as usize;
-            self.draw_image_group(framebuffer, sprite_sheet, image_group);
+            self.draw_image_group(framebuffer, sprite_sheet, image_group)?;
         }
+
+        Ok(())
     }
 
     pub fn display(&self) {
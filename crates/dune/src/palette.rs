@@ -1,8 +1,4 @@
-use std::io::{Cursor, Seek};
-
-use bytes_ext::ReadBytesExt;
-
-use crate::Color;
+use crate::{Color, Error, resource_reader::ResourceReader};
 
 #[derive(Debug, Clone)]
 pub struct Palette([Color; 256]);
@@ -11,6 +7,12 @@ fn scale_6bit_to_8bit(c: u8) -> u8 {
     (255 * (c as u16) / 63) as u8
 }
 
+/// Rounds to the nearest 6-bit value, so 6-bit colors scaled up with
+/// `scale_6bit_to_8bit` come back unchanged.
+fn scale_8bit_to_6bit(c: u8) -> u8 {
+    ((63 * (c as u16) + 127) / 255) as u8
+}
+
 impl Palette {
     pub fn new() -> Self {
         Self([Color::default(); 256])
@@ -46,6 +48,48 @@ impl Palette {
         }
     }
 
+    /// A palette from 256 VGA DAC colors with 6-bit components, as stored
+    /// by the game.
+    pub fn from_6bit(pal: &[u8; 768]) -> Self {
+        let mut palette = Self::new();
+        palette.set_all(pal);
+        palette
+    }
+
+    pub fn to_6bit(&self) -> [u8; 768] {
+        let mut pal = [0; 768];
+        for (dst, c) in pal.chunks_exact_mut(3).zip(&self.0) {
+            dst.copy_from_slice(&[c.0, c.1, c.2]);
+        }
+        pal
+    }
+
+    /// A palette from 256 colors with 8-bit components, rounded to the
+    /// nearest 6-bit color.
+    pub fn from_rgb888(pal: &[u8; 768]) -> Self {
+        let mut palette = Self::new();
+        for (i, c) in pal.chunks_exact(3).enumerate() {
+            palette.set(
+                i,
+                Color(
+                    scale_8bit_to_6bit(c[0]),
+                    scale_8bit_to_6bit(c[1]),
+                    scale_8bit_to_6bit(c[2]),
+                ),
+            );
+        }
+        palette
+    }
+
+    pub fn to_rgb888(&self) -> [u8; 768] {
+        let mut pal = [0; 768];
+        for (i, dst) in pal.chunks_exact_mut(3).enumerate() {
+            let c = self.get_rgb888(i);
+            dst.copy_from_slice(&[c.0, c.1, c.2]);
+        }
+        pal
+    }
+
     pub fn as_slice(&self) -> &[Color; 256] {
         &self.0
     }
@@ -54,57 +98,30 @@ impl Palette {
         &mut self.0
     }
 
-    pub fn apply_palette_update(&mut self, data: &[u8]) -> Result<u64, std::io::Error> {
-        let mut r = Cursor::new(data);
-
-        loop {
-            let read_u8 = r.read_u8();
-            let index = read_u8? as usize;
-            let mut count = r.read_u8()? as usize;
-
-            if index == 1 && count == 0 {
-                r.seek_relative(3)?;
-                continue;
-            }
-            if index == 0xff && count == 0xff {
-                break;
-            }
-            if count == 0 {
-                count = 256;
-            }
-
-            for i in 0..count {
-                let cr = r.read_u8()?;
-                let cg = r.read_u8()?;
-                let cb = r.read_u8()?;
+    pub fn apply_palette_update(&mut self, data: &[u8]) -> Result<u64, Error> {
+        read_palette_update(data, |index, color| self.set(index, color))
+    }
 
-                if index + i <= 255 {
-                    self.set(index + i, Color(cr, cg, cb));
-                }
-            }
-        }
+    /// The indices a palette update sets, in ascending order.
+    pub fn palette_update_indices(data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut updated = [false; 256];
+        read_palette_update(data, |index, _| updated[index] = true)?;
 
-        loop {
-            match r.read_u8() {
-                Ok(0xff) => {}
-                Ok(_) => {
-                    r.seek_relative(-1)?;
-                    break;
-                }
-                Err(_) => {
-                    break;
-                }
-            }
-        }
-
-        Ok(r.position())
+        Ok((0..=255).filter(|&i| updated[i as usize]).collect())
     }
 
     pub fn find_closest_color(&self, color: Color) -> u8 {
+        self.find_closest_color_in(color, 0..=255)
+    }
+
+    /// Like [`Palette::find_closest_color`], but only considers the given
+    /// indices. Ties go to the first index.
+    pub fn find_closest_color_in(&self, color: Color, indices: impl IntoIterator<Item = u8>) -> u8 {
         let mut best_index = 0;
         let mut best_distance = u32::MAX;
 
-        for (i, &c) in self.0.iter().enumerate() {
+        for i in indices {
+            let c = self.0[i as usize];
             let dr = c.0 as i16 - color.0 as i16;
             let dg = c.1 as i16 - color.1 as i16;
             let db = c.2 as i16 - color.2 as i16;
@@ -117,7 +134,22 @@ impl Palette {
             }
         }
 
-        best_index as u8
+        best_index
+    }
+
+    /// Maps RGBA pixels to the closest color among `indices`. Pixels that
+    /// are more than half transparent map to index 0.
+    pub fn quantize(&self, pixels: &[[u8; 4]], indices: &[u8]) -> Vec<u8> {
+        pixels
+            .iter()
+            .map(|&[r, g, b, a]| {
+                if a < 128 {
+                    return 0;
+                }
+                let color = Color(r >> 2, g >> 2, b >> 2);
+                self.find_closest_color_in(color, indices.iter().copied())
+            })
+            .collect()
     }
 
     pub fn copy_from(&mut self, other: &Self) {
@@ -125,8 +157,87 @@ impl Palette {
     }
 }
 
+/// Reads a palette update, calling `set` for each color it sets. Returns
+/// the size of the update.
+fn read_palette_update(data: &[u8], mut set: impl FnMut(usize, Color)) -> Result<u64, Error> {
+    let mut r = ResourceReader::new("palette update", data);
+
+    loop {
+        let index = r.read_u8()? as usize;
+        let mut count = r.read_u8()? as usize;
+
+        if index == 1 && count == 0 {
+            r.skip(3)?;
+            continue;
+        }
+        if index == 0xff && count == 0xff {
+            break;
+        }
+        if count == 0 {
+            count = 256;
+        }
+
+        for i in 0..count {
+            let cr = r.read_u8()?;
+            let cg = r.read_u8()?;
+            let cb = r.read_u8()?;
+
+            if index + i <= 255 {
+                set(index + i, Color(cr, cg, cb));
+            }
+        }
+    }
+
+    while r.remaining().first() == Some(&0xff) {
+        r.skip(1)?;
+    }
+
+    Ok(r.position() as u64)
+}
+
 impl Default for Palette {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_update_indices() {
+        // Colors 0x10 and 0x11, a skipped entry and color 0xff
+        let update = [
+            0x10, 0x02, 1, 2, 3, 4, 5, 6, 0x01, 0x00, 0, 0, 0, 0xff, 0x01, 7, 8, 9, 0xff, 0xff,
+        ];
+        assert_eq!(
+            Palette::palette_update_indices(&update).unwrap(),
+            [0x10, 0x11, 0xff]
+        );
+
+        let mut pal = Palette::new();
+        assert_eq!(pal.apply_palette_update(&update).unwrap(), 20);
+        assert_eq!(pal.get(0x11), Color(4, 5, 6));
+        assert_eq!(pal.get(0xff), Color(7, 8, 9));
+
+        assert!(Palette::palette_update_indices(&update[..4]).is_err());
+    }
+
+    #[test]
+    fn test_quantize() {
+        let mut pal = Palette::new();
+        pal.set(1, Color(63, 0, 0));
+        pal.set(2, Color(0, 0, 63));
+        pal.set(3, Color(60, 0, 0));
+
+        let pixels = [
+            [255, 0, 0, 255],
+            [240, 0, 0, 255],
+            [0, 0, 200, 128],
+            [255, 0, 0, 127],
+        ];
+        assert_eq!(pal.quantize(&pixels, &[1, 2, 3]), [1, 3, 2, 0]);
+        assert_eq!(pal.quantize(&pixels, &[2, 3]), [3, 3, 2, 0]);
+    }
+}
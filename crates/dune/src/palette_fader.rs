@@ -0,0 +1,198 @@
+use std::ops::Range;
+
+use crate::{Color, Palette};
+
+/// One step of the game's palette transition (`gfx_vtable_func_39`).
+///
+/// Each component of the colors in `range` moves `1/divisor` of the way
+/// to `target`, truncated towards the current color. A divisor of 0 is
+/// treated as 1, which sets the colors to the target.
+pub fn transition_palette(pal: &mut Palette, target: &Palette, range: Range<usize>, divisor: u16) {
+    let divisor = divisor.clamp(1, i16::MAX as u16) as i16;
+
+    for i in range {
+        pal.set(i, pal.get(i).lerp(target.get(i), divisor));
+    }
+}
+
+/// A timed fade of a palette range towards a target palette.
+///
+/// A fade over `n` steps divides by `n`, `n - 1`, ..., 1, the way the game
+/// counts its timers down, so the last step lands exactly on the target.
+#[derive(Debug, Clone)]
+pub struct PaletteFader {
+    target: Palette,
+    range: Range<usize>,
+    steps: u16,
+}
+
+impl PaletteFader {
+    pub fn new(target: Palette, range: Range<usize>, steps: u16) -> Self {
+        Self {
+            target,
+            range,
+            steps,
+        }
+    }
+
+    pub fn to_black(range: Range<usize>, steps: u16) -> Self {
+        Self::new(Palette::new(), range, steps)
+    }
+
+    /// Clears `range` of `pal` to black and fades it back to `target`.
+    pub fn from_black(pal: &mut Palette, target: Palette, range: Range<usize>, steps: u16) -> Self {
+        for i in range.clone() {
+            pal.set(i, Color::default());
+        }
+        Self::new(target, range, steps)
+    }
+
+    pub fn target(&self) -> &Palette {
+        &self.target
+    }
+
+    pub fn remaining_steps(&self) -> u16 {
+        self.steps
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.steps == 0
+    }
+
+    /// Advances the fade by one step. Returns false if it was already done.
+    pub fn step(&mut self, pal: &mut Palette) -> bool {
+        if self.steps == 0 {
+            return false;
+        }
+
+        transition_palette(pal, &self.target, self.range.clone(), self.steps);
+        self.steps -= 1;
+
+        true
+    }
+}
+
+/// A range of palette entries rotated every `delay` ticks.
+#[derive(Debug, Clone)]
+pub struct CycleRange {
+    pub range: Range<usize>,
+    pub delay: u16,
+    /// Rotate towards lower indices instead of higher ones.
+    pub reverse: bool,
+    ticks: u16,
+}
+
+impl CycleRange {
+    pub fn new(range: Range<usize>, delay: u16) -> Self {
+        Self {
+            range,
+            delay,
+            reverse: false,
+            ticks: 0,
+        }
+    }
+
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+}
+
+/// Color cycling of one or more palette ranges.
+#[derive(Debug, Clone, Default)]
+pub struct PaletteCycler {
+    ranges: Vec<CycleRange>,
+}
+
+impl PaletteCycler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_range(&mut self, range: CycleRange) {
+        self.ranges.push(range);
+    }
+
+    pub fn ranges(&self) -> &[CycleRange] {
+        &self.ranges
+    }
+
+    /// Advances all ranges by one tick. Returns true if the palette changed.
+    pub fn tick(&mut self, pal: &mut Palette) -> bool {
+        let mut changed = false;
+
+        for range in &mut self.ranges {
+            range.ticks += 1;
+            if range.ticks < range.delay.max(1) {
+                continue;
+            }
+            range.ticks = 0;
+
+            let colors = &mut pal.as_mut_slice()[range.range.clone()];
+            if range.reverse {
+                colors.rotate_left(1);
+            } else {
+                colors.rotate_right(1);
+            }
+            changed |= !colors.is_empty();
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fader() {
+        let mut pal = Palette::new();
+        pal.set(1, Color(63, 10, 0));
+        let mut target = Palette::new();
+        target.set(0, Color(63, 63, 63));
+        target.set(1, Color(0, 10, 63));
+
+        let mut fader = PaletteFader::new(target.clone(), 0..2, 4);
+        let mut steps = Vec::new();
+        while fader.step(&mut pal) {
+            steps.push((pal.get(0), pal.get(1)));
+        }
+
+        assert_eq!(
+            steps,
+            [
+                (Color(15, 15, 15), Color(48, 10, 15)),
+                (Color(31, 31, 31), Color(32, 10, 31)),
+                (Color(47, 47, 47), Color(16, 10, 47)),
+                (Color(63, 63, 63), Color(0, 10, 63)),
+            ]
+        );
+        assert!(fader.is_done());
+
+        let mut fader = PaletteFader::to_black(1..2, 2);
+        fader.step(&mut pal);
+        assert_eq!(pal.get(0), Color(63, 63, 63));
+        assert_eq!(pal.get(1), Color(0, 5, 32));
+    }
+
+    #[test]
+    fn test_cycler() {
+        let mut pal = Palette::new();
+        for i in 0..4 {
+            pal.set(i, Color(i as u8, 0, 0));
+        }
+
+        let mut cycler = PaletteCycler::new();
+        cycler.add_range(CycleRange::new(0..3, 2));
+        cycler.add_range(CycleRange::new(2..4, 1).reverse(true));
+
+        assert!(cycler.tick(&mut pal));
+        let reds: Vec<u8> = pal.as_slice()[..4].iter().map(|c| c.0).collect();
+        assert_eq!(reds, [0, 1, 3, 2]);
+
+        assert!(cycler.tick(&mut pal));
+        let reds: Vec<u8> = pal.as_slice()[..4].iter().map(|c| c.0).collect();
+        assert_eq!(reds, [3, 0, 2, 1]);
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt::Write;
+
+use crate::{Error, Palette};
+
+/// Palette file formats used by paint programs.
+///
+/// The text formats and Adobe .act store 8-bit colors, which are rounded to
+/// the game's 6-bit colors on import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaletteFormat {
+    /// JASC-PAL, the text .pal format of Paint Shop Pro, also read by
+    /// Aseprite and most other editors.
+    Jasc,
+    /// GIMP .gpl text format.
+    Gimp,
+    /// Adobe .act, 256 8-bit RGB triplets, optionally followed by a color
+    /// count and a transparent index.
+    Act,
+    /// Raw VGA DAC .pal, 256 6-bit RGB triplets as used by the game.
+    Vga,
+}
+
+impl PaletteFormat {
+    /// The format to write for a file extension. Both JASC-PAL and VGA
+    /// palettes use .pal, which is taken to be JASC-PAL.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "pal" => Some(PaletteFormat::Jasc),
+            "gpl" => Some(PaletteFormat::Gimp),
+            "act" => Some(PaletteFormat::Act),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format of a palette file from its contents.
+    ///
+    /// Binary files of 768 bytes are taken to be VGA palettes if no
+    /// component exceeds 63.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"JASC-PAL") {
+            return Some(PaletteFormat::Jasc);
+        }
+        if data.starts_with(b"GIMP Palette") {
+            return Some(PaletteFormat::Gimp);
+        }
+        match data.len() {
+            768 if data.iter().all(|&c| c < 64) => Some(PaletteFormat::Vga),
+            768 | 772 => Some(PaletteFormat::Act),
+            _ => None,
+        }
+    }
+
+    /// Reads a palette. Colors missing from the file are black.
+    pub fn read(self, data: &[u8]) -> Result<Palette, Error> {
+        match self {
+            PaletteFormat::Jasc => read_jasc(data),
+            PaletteFormat::Gimp => read_gimp(data),
+            PaletteFormat::Act => read_act(data),
+            PaletteFormat::Vga => read_vga(data),
+        }
+    }
+
+    pub fn write(self, pal: &Palette) -> Vec<u8> {
+        match self {
+            PaletteFormat::Jasc => write_jasc(pal),
+            PaletteFormat::Gimp => write_gimp(pal),
+            PaletteFormat::Act => pal.to_rgb888().to_vec(),
+            PaletteFormat::Vga => pal.to_6bit().to_vec(),
+        }
+    }
+}
+
+fn invalid_data(offset: usize, reason: &'static str) -> Error {
+    Error::InvalidData {
+        resource: "palette".to_owned(),
+        offset,
+        reason,
+    }
+}
+
+/// The lines of a text file with the offset each starts at.
+fn lines(data: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    data.split(|&c| c == b'\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len() + 1;
+        Some((start, line.strip_suffix(b"\r").unwrap_or(line)))
+    })
+}
+
+/// Parses the first three whitespace separated numbers of a line as an
+/// 8-bit color.
+fn parse_rgb(offset: usize, line: &[u8]) -> Result<[u8; 3], Error> {
+    let invalid = || invalid_data(offset, "invalid color");
+
+    let line = std::str::from_utf8(line).map_err(|_| invalid())?;
+    let mut components = line.split_whitespace().map(|c| c.parse::<u8>());
+    let mut rgb = [0; 3];
+    for c in &mut rgb {
+        *c = components.next().and_then(|c| c.ok()).ok_or_else(invalid)?;
+    }
+
+    Ok(rgb)
+}
+
+fn from_colors(colors: &[[u8; 3]]) -> Palette {
+    let mut pal = [0; 768];
+    for (dst, c) in pal.chunks_exact_mut(3).zip(colors) {
+        dst.copy_from_slice(c);
+    }
+    Palette::from_rgb888(&pal)
+}
+
+fn read_jasc(data: &[u8]) -> Result<Palette, Error> {
+    let mut lines = lines(data);
+
+    let mut header = |expected: Option<&[u8]>| {
+        let (offset, line) = lines
+            .next()
+            .ok_or_else(|| invalid_data(data.len(), "missing header"))?;
+        match expected {
+            Some(expected) if line != expected => Err(invalid_data(offset, "invalid header")),
+            _ => Ok((offset, line)),
+        }
+    };
+    header(Some(b"JASC-PAL"))?;
+    header(Some(b"0100"))?;
+    let (offset, line) = header(None)?;
+    let count = std::str::from_utf8(line)
+        .ok()
+        .and_then(|count| count.trim().parse::<usize>().ok())
+        .filter(|&count| count <= 256)
+        .ok_or_else(|| invalid_data(offset, "invalid color count"))?;
+
+    let mut colors = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (offset, line) = lines.next().ok_or_else(|| Error::Truncated {
+            resource: "palette".to_owned(),
+            offset: data.len(),
+        })?;
+        colors.push(parse_rgb(offset, line)?);
+    }
+
+    Ok(from_colors(&colors))
+}
+
+fn read_gimp(data: &[u8]) -> Result<Palette, Error> {
+    let mut lines = lines(data);
+    if lines.next().is_none_or(|(_, line)| line != b"GIMP Palette") {
+        return Err(invalid_data(0, "invalid header"));
+    }
+
+    let mut colors = Vec::new();
+    for (offset, line) in lines {
+        let trimmed = line.trim_ascii();
+        if trimmed.is_empty()
+            || trimmed.starts_with(b"#")
+            || trimmed.starts_with(b"Name:")
+            || trimmed.starts_with(b"Columns:")
+        {
+            continue;
+        }
+        if colors.len() == 256 {
+            return Err(invalid_data(offset, "more than 256 colors"));
+        }
+        colors.push(parse_rgb(offset, line)?);
+    }
+
+    Ok(from_colors(&colors))
+}
+
+fn read_act(data: &[u8]) -> Result<Palette, Error> {
+    let count = match data.len() {
+        768 => 256,
+        772 => u16::from_be_bytes([data[768], data[769]]).clamp(1, 256) as usize,
+        _ => {
+            return Err(Error::SizeMismatch {
+                resource: "palette".to_owned(),
+                expected: 768,
+                actual: data.len(),
+            });
+        }
+    };
+
+    let colors: Vec<[u8; 3]> = data[..count * 3]
+        .chunks_exact(3)
+        .map(|c| [c[0], c[1], c[2]])
+        .collect();
+
+    Ok(from_colors(&colors))
+}
+
+fn read_vga(data: &[u8]) -> Result<Palette, Error> {
+    let pal: &[u8; 768] = data.try_into().map_err(|_| Error::SizeMismatch {
+        resource: "palette".to_owned(),
+        expected: 768,
+        actual: data.len(),
+    })?;
+    if let Some(offset) = pal.iter().position(|&c| c > 63) {
+        return Err(invalid_data(offset, "color component above 63"));
+    }
+
+    Ok(Palette::from_6bit(pal))
+}
+
+fn write_jasc(pal: &Palette) -> Vec<u8> {
+    let mut s = String::from("JASC-PAL\r\n0100\r\n256\r\n");
+    for c in pal.to_rgb888().chunks_exact(3) {
+        let _ = write!(s, "{} {} {}\r\n", c[0], c[1], c[2]);
+    }
+    s.into_bytes()
+}
+
+fn write_gimp(pal: &Palette) -> Vec<u8> {
+    let mut s = String::from("GIMP Palette\nName: Dune\nColumns: 16\n#\n");
+    for (i, c) in pal.to_rgb888().chunks_exact(3).enumerate() {
+        let _ = writeln!(s, "{:3} {:3} {:3}\tIndex {i}", c[0], c[1], c[2]);
+    }
+    s.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    const FORMATS: [PaletteFormat; 4] = [
+        PaletteFormat::Jasc,
+        PaletteFormat::Gimp,
+        PaletteFormat::Act,
+        PaletteFormat::Vga,
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        let mut pal = Palette::new();
+        for i in 0..256 {
+            pal.set(i, Color(i as u8 % 64, (i / 4) as u8, 63 - (i as u8 % 64)));
+        }
+
+        for format in FORMATS {
+            let data = format.write(&pal);
+            assert_eq!(PaletteFormat::detect(&data), Some(format));
+            let read = format.read(&data).unwrap();
+            assert_eq!(read.as_slice(), pal.as_slice(), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn test_read() {
+        let gpl = b"GIMP Palette\nName: Test\n#\n255 0 0\tRed\n  0 255   0\n";
+        let pal = PaletteFormat::Gimp.read(gpl).unwrap();
+        assert_eq!(pal.get(0), Color(63, 0, 0));
+        assert_eq!(pal.get(1), Color(0, 63, 0));
+        assert_eq!(pal.get(2), Color(0, 0, 0));
+
+        let jasc = b"JASC-PAL\r\n0100\r\n2\r\n4 8 12\r\n";
+        assert!(matches!(
+            PaletteFormat::Jasc.read(jasc),
+            Err(Error::Truncated { .. })
+        ));
+        let jasc = b"JASC-PAL\r\n0100\r\n1\r\n4 8 x\r\n";
+        assert!(matches!(
+            PaletteFormat::Jasc.read(jasc),
+            Err(Error::InvalidData { offset: 19, .. })
+        ));
+
+        let mut act = vec![0; 772];
+        act[..6].copy_from_slice(&[255, 255, 255, 128, 128, 128]);
+        act[6..9].copy_from_slice(&[255, 0, 0]);
+        act[768..].copy_from_slice(&[0, 2, 0xff, 0xff]);
+        let pal = PaletteFormat::Act.read(&act).unwrap();
+        assert_eq!(pal.get(1), Color(32, 32, 32));
+        assert_eq!(pal.get(2), Color(0, 0, 0));
+
+        assert!(PaletteFormat::Vga.read(&[64; 768]).is_err());
+        assert!(PaletteFormat::Vga.read(&[0; 767]).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+
+use crate::{Lipsync, SpriteSheet};
+
+const VOC_MAGIC: &[u8] = b"Creative Voice File\x1a";
+
+/// The kind of data stored in a game resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    SpriteSheet,
+    RoomSheet,
+    Hnm,
+    /// A sprite sheet whose last resource holds lipsync animations.
+    Lipsync,
+    Palette,
+    Globe,
+    Font,
+    Voc,
+    Savegame,
+    Unknown,
+}
+
+impl ResourceKind {
+    /// Guesses the kind of a resource from its name and its (decompressed)
+    /// contents.
+    ///
+    /// Formats without a reliable signature are recognised by name, the rest
+    /// by trying to parse the data.
+    pub fn detect(name: &str, data: &[u8]) -> ResourceKind {
+        let name = name
+            .rsplit(['\\', '/'])
+            .next()
+            .unwrap_or(name)
+            .to_ascii_uppercase();
+        let (stem, ext) = name.rsplit_once('.').unwrap_or((&name, ""));
+
+        match ext {
+            "HNM" => return ResourceKind::Hnm,
+            "SAL" => return ResourceKind::RoomSheet,
+            "VOC" => return ResourceKind::Voc,
+            "SAV" => return ResourceKind::Savegame,
+            _ => {}
+        }
+
+        match stem {
+            "PAL" => return ResourceKind::Palette,
+            "GLOBDATA" | "TABLAT" | "MAP" => return ResourceKind::Globe,
+            "DUNECHAR" => return ResourceKind::Font,
+            _ => {}
+        }
+
+        if data.starts_with(VOC_MAGIC) {
+            return ResourceKind::Voc;
+        }
+
+        if let Ok(sprite_sheet) = SpriteSheet::from_slice(data) {
+            if has_lipsync(&sprite_sheet) {
+                return ResourceKind::Lipsync;
+            }
+            if (0..sprite_sheet.resource_count()).any(|id| sprite_sheet.get_sprite(id).is_some()) {
+                return ResourceKind::SpriteSheet;
+            }
+        }
+
+        ResourceKind::Unknown
+    }
+}
+
+fn has_lipsync(sprite_sheet: &SpriteSheet) -> bool {
+    let Some(last_resource_id) = sprite_sheet.resource_count().checked_sub(1) else {
+        return false;
+    };
+
+    sprite_sheet
+        .get_resource(last_resource_id)
+        .and_then(|data| Lipsync::from_bytes(data).ok())
+        .is_some_and(|lipsync| lipsync.animation_count() > 0)
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ResourceKind::SpriteSheet => "sprite sheet",
+            ResourceKind::RoomSheet => "room sheet",
+            ResourceKind::Hnm => "hnm video",
+            ResourceKind::Lipsync => "lipsync portrait",
+            ResourceKind::Palette => "palette",
+            ResourceKind::Globe => "globe tables",
+            ResourceKind::Font => "font",
+            ResourceKind::Voc => "voc audio",
+            ResourceKind::Savegame => "savegame",
+            ResourceKind::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let sprite_sheet = [
+            0x02, 0x00, // toc_pos
+            0x02, 0x00, // sprite 0 offset
+            0x02, 0x00, 0x01, 0x00, // 2x1 sprite
+            0x12,
+        ];
+
+        assert_eq!(ResourceKind::detect("CRYO.HNM", &[]), ResourceKind::Hnm);
+        assert_eq!(
+            ResourceKind::detect("palace.sal", &[]),
+            ResourceKind::RoomSheet
+        );
+        assert_eq!(
+            ResourceKind::detect("DUNE\\PAL.BIN", &[]),
+            ResourceKind::Palette
+        );
+        assert_eq!(
+            ResourceKind::detect("GLOBDATA.HSQ", &[]),
+            ResourceKind::Globe
+        );
+        assert_eq!(
+            ResourceKind::detect("SOUND.BIN", b"Creative Voice File\x1a\x1a\x00"),
+            ResourceKind::Voc
+        );
+        assert_eq!(
+            ResourceKind::detect("ORNY.HSQ", &sprite_sheet),
+            ResourceKind::SpriteSheet
+        );
+        assert_eq!(
+            ResourceKind::detect("TEXT.HSQ", &[0x02, 0x00, 0x02, 0x00, 0x41]),
+            ResourceKind::Unknown
+        );
+        assert_eq!(ResourceKind::detect("EMPTY", &[]), ResourceKind::Unknown);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::Error;
+
+/// Bounds-checked reader over an in-memory resource.
+///
+/// Every read that runs past the end of the data returns
+/// [`Error::Truncated`] with the offset of the failed read.
+pub(crate) struct ResourceReader<'a> {
+    resource: &'static str,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ResourceReader<'a> {
+    pub fn new(resource: &'static str, data: &'a [u8]) -> Self {
+        Self {
+            resource,
+            data,
+            pos: 0,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn set_position(&mut self, pos: usize) -> Result<(), Error> {
+        if pos > self.data.len() {
+            return Err(self.bad_offset(pos));
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
+    pub fn skip(&mut self, count: usize) -> Result<(), Error> {
+        self.read_slice(count).map(|_| ())
+    }
+
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos.min(self.data.len())..]
+    }
+
+    pub fn read_slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let slice = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| self.truncated())?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_slice(1)?[0])
+    }
+
+    pub fn read_i8(&mut self) -> Result<i8, Error> {
+        Ok(self.read_u8()? as i8)
+    }
+
+    pub fn read_le_u16(&mut self) -> Result<u16, Error> {
+        let b = self.read_slice(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn read_be_u16(&mut self) -> Result<u16, Error> {
+        let b = self.read_slice(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    pub fn read_le_u32(&mut self) -> Result<u32, Error> {
+        let b = self.read_slice(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn truncated(&self) -> Error {
+        Error::Truncated {
+            resource: self.resource.to_owned(),
+            offset: self.pos,
+        }
+    }
+
+    pub fn bad_offset(&self, offset: usize) -> Error {
+        Error::BadOffset {
+            resource: self.resource.to_owned(),
+            offset,
+        }
+    }
+
+    pub fn invalid_data(&self, reason: &'static str) -> Error {
+        Error::InvalidData {
+            resource: self.resource.to_owned(),
+            offset: self.pos,
+            reason,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use crate::{image::Image, scaler::Scaler};
+
+/// A true-color image with 8-bit red, green, blue and alpha channels, as
+/// used by canvases and PNG files.
+pub type RgbaImage = Image<[u8; 4]>;
+
+impl RgbaImage {
+    /// The pixels as consecutive RGBA bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.pixels.as_flattened()
+    }
+
+    /// Reads a PNG file of any color type as 8-bit RGBA.
+    pub fn read_png<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(
+            png::Transformations::normalize_to_color8() | png::Transformations::ALPHA,
+        );
+
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size().unwrap_or_default()];
+        let info = reader.next_frame(&mut buf)?;
+        buf.truncate(info.buffer_size());
+
+        let pixels: Vec<[u8; 4]> = match info.color_type {
+            png::ColorType::Rgba => buf
+                .chunks_exact(4)
+                .map(|p| [p[0], p[1], p[2], p[3]])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buf
+                .chunks_exact(2)
+                .map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            color_type => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("unsupported png color type {color_type:?}"),
+                ));
+            }
+        };
+
+        let (Ok(w), Ok(h)) = (u16::try_from(info.width), u16::try_from(info.height)) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "image too large",
+            ));
+        };
+
+        Ok(Self {
+            w,
+            h,
+            pixels: pixels.into_boxed_slice(),
+        })
+    }
+}
+
+/// How [`Framebuffer::write_rgba`](crate::Framebuffer::write_rgba) converts
+/// a framebuffer.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RgbaOptions {
+    pub scaler: Scaler,
+    /// Make pixels with index 0 transparent.
+    pub transparent_zero: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, Framebuffer, Palette};
+
+    #[test]
+    fn test_to_rgba() {
+        let mut pal = Palette::new();
+        pal.set(1, Color(63, 0, 0));
+
+        let mut framebuffer = Framebuffer::new(2, 5);
+        framebuffer.set(1, 0, 1);
+
+        let image = framebuffer.to_rgba(&pal, 1, false).unwrap();
+        assert_eq!((image.w(), image.h()), (2, 5));
+        assert_eq!(&image.as_bytes()[..8], [0, 0, 0, 255, 255, 0, 0, 255]);
+
+        let image = framebuffer.to_rgba(&pal, 2, true).unwrap();
+        assert_eq!((image.w(), image.h()), (4, 12));
+        // The first source row covers three rows after stretching
+        assert_eq!(image.get(2, 2), [255, 0, 0, 255]);
+        assert_eq!(image.get(2, 3), [0, 0, 0, 255]);
+
+        let mut image = RgbaImage::new(0, 0);
+        let options = RgbaOptions {
+            transparent_zero: true,
+            ..RgbaOptions::default()
+        };
+        framebuffer.write_rgba(&pal, options, &mut image).unwrap();
+        assert_eq!(image.get(0, 0), [0, 0, 0, 0]);
+        assert_eq!(image.get(1, 0), [255, 0, 0, 255]);
+    }
+}
@@ -1,17 +1,16 @@
 #![allow(clippy::too_many_arguments)]
 
 mod galois_noise_generator;
-mod room;
+pub mod room;
 mod room_sheet;
 
-use std::{io::Cursor, mem::swap};
+use std::mem::swap;
 
-use bytes_ext::ReadBytesExt;
 pub use room::Room;
 pub use room_sheet::RoomSheet;
 
 use crate::{
-    Color, Framebuffer, IndexMap, Palette, Point, SpriteSheet,
+    Framebuffer, IndexMap, Palette, Point, Sky, SpriteSheet,
     room_renderer::room::{Part, Polygon},
     sprite_blitter,
 };
@@ -19,11 +18,22 @@ use crate::{
 pub struct RoomRenderer {
     room: Option<Room>,
     sprite_sheet: Option<SpriteSheet>,
+    characters: Vec<Option<RoomCharacter>>,
     y_offset: i16,
+    game_time: u16,
+    game_time_fraction: u8,
+}
+
+/// A character drawn at a character position of a room.
+pub struct RoomCharacter {
+    pub sprite_sheet: SpriteSheet,
+    /// The sprite to draw.
+    pub pose: u16,
 }
 
 pub struct DrawOptions {
     pub draw_sprites: bool,
+    pub draw_characters: bool,
     pub draw_polygons: bool,
     pub draw_lines: bool,
 }
@@ -32,6 +42,7 @@ impl Default for DrawOptions {
     fn default() -> Self {
         Self {
             draw_sprites: true,
+            draw_characters: true,
             draw_polygons: true,
             draw_lines: true,
         }
@@ -43,7 +54,10 @@ impl RoomRenderer {
         Self {
             room: None,
             sprite_sheet: None,
+            characters: Vec::new(),
             y_offset: 0,
+            game_time: 0,
+            game_time_fraction: 0,
         }
     }
 
@@ -55,6 +69,25 @@ impl RoomRenderer {
         self.sprite_sheet = Some(sprite_sheet);
     }
 
+    /// Sets the characters drawn at the room's character positions, in the
+    /// order the positions appear. A `None` leaves its position empty, as
+    /// do positions past the last character.
+    pub fn set_characters(&mut self, characters: Vec<Option<RoomCharacter>>) {
+        self.characters = characters;
+    }
+
+    pub fn characters(&self) -> &[Option<RoomCharacter>] {
+        &self.characters
+    }
+
+    /// Sets the time of day the sky is drawn at, from a game time as stored
+    /// in savegames. `fraction` is how far the time is towards the next
+    /// step, in 256ths.
+    pub fn set_game_time(&mut self, game_time: u16, fraction: u8) {
+        self.game_time = game_time;
+        self.game_time_fraction = fraction;
+    }
+
     pub fn get_sprite_sheet(&mut self) -> Option<&SpriteSheet> {
         self.sprite_sheet.as_ref()
     }
@@ -66,8 +99,8 @@ impl RoomRenderer {
         pal: &Palette,
         frame: &mut Framebuffer,
     ) -> Result<(), std::io::Error> {
-        for (i, part) in room.parts().iter().enumerate() {
-            self.draw_part(i, part, sprite_sheet, frame, None)?;
+        for (i, (part, character)) in self.parts_with_characters(room).enumerate() {
+            self.draw_part(i, part, sprite_sheet, character, frame, None)?;
 
             let filename = format!("room-part-{i:02}.ppm");
             frame.write_ppm_scaled(pal, &filename)?;
@@ -76,41 +109,16 @@ impl RoomRenderer {
         Ok(())
     }
 
+    /// Sets the sky colors for the game time and draws the sky backdrop
+    /// behind the room.
     pub fn draw_sky(
         &self,
-        sky_asset: &[u8],
-        sky_palette_index: usize,
+        sky: &Sky,
         pal: &mut Palette,
-        // frame: &mut Framebuffer,
-    ) {
-        let mut c = Cursor::new(sky_asset);
-        let toc_pos = c.read_le_u16().unwrap() as u64;
-        c.set_position(toc_pos + (8 + sky_palette_index.min(32) as u64) * 2);
-        let sub_ofs = c.read_le_u16().unwrap() as u64;
-        c.set_position(toc_pos + sub_ofs + 6);
-
-        let pal_ofs = 73;
-        let pal_cnt = 151;
-        for i in 0..pal_cnt {
-            let r = c.read_u8().unwrap();
-            let g = c.read_u8().unwrap();
-            let b = c.read_u8().unwrap();
-
-            pal.set(pal_ofs + i, Color(r, g, b));
-        }
-
-        // let sky_sprite_sheet = SpriteSheet::new(sky_asset).unwrap();
-
-        // for sprite_id in 0..4 {
-        //     let sprite = sky_sprite_sheet.get_sprite(sprite_id).unwrap();
-        //     for col in 0..8 {
-        //         let y = 20 * sprite_id;
-        //         let x = 40 * col;
-        //         sprite
-        //             .draw(sprite_id, x, y, false, false, 0, 0, frame, &mut None)
-        //             .unwrap();
-        //     }
-        // }
+        frame: &mut Framebuffer,
+    ) -> Result<(), std::io::Error> {
+        sky.apply_game_time(self.game_time, self.game_time_fraction, pal);
+        sky.draw_backdrop(self.y_offset, frame)
     }
 
     pub fn draw(
@@ -127,19 +135,43 @@ impl RoomRenderer {
         };
 
         let mut index_map = index_map;
-        for (i, part) in room.parts().iter().enumerate() {
+        for (i, (part, character)) in self.parts_with_characters(room).enumerate() {
             if Self::should_draw(options, part) {
-                self.draw_part(i, part, sprite_sheet, frame, index_map.as_deref_mut())?;
+                self.draw_part(
+                    i,
+                    part,
+                    sprite_sheet,
+                    character,
+                    frame,
+                    index_map.as_deref_mut(),
+                )?;
             }
         }
 
         Ok(())
     }
 
+    /// The room's parts, each with the character drawn at it. Characters
+    /// are assigned to character parts in order.
+    fn parts_with_characters<'a>(
+        &'a self,
+        room: &'a Room,
+    ) -> impl Iterator<Item = (&'a Part, Option<&'a RoomCharacter>)> {
+        room.parts()
+            .iter()
+            .scan(self.characters.iter(), |characters, part| {
+                let character = match part {
+                    Part::Character(_) => characters.next().and_then(Option::as_ref),
+                    _ => None,
+                };
+                Some((part, character))
+            })
+    }
+
     fn should_draw(options: &DrawOptions, part: &Part) -> bool {
         match part {
             Part::Sprite(_) => options.draw_sprites,
-            Part::Character(_) => true,
+            Part::Character(_) => options.draw_characters,
             Part::Polygon(_) => options.draw_polygons,
             Part::Line(_) => options.draw_lines,
         }
@@ -150,6 +182,7 @@ impl RoomRenderer {
         index: usize,
         part: &Part,
         sprite_sheet: &SpriteSheet,
+        character: Option<&RoomCharacter>,
         framebuffer: &mut Framebuffer,
         index_map: Option<&mut IndexMap>,
     ) -> Result<(), std::io::Error> {
@@ -171,7 +204,24 @@ impl RoomRenderer {
                     .index_map(index_map)
                     .draw()?;
             }
-            Part::Character(_) => {}
+            Part::Character(character_part) => {
+                let Some(sprite) = character.and_then(|c| c.sprite_sheet.get_sprite(c.pose)) else {
+                    return Ok(());
+                };
+
+                sprite_blitter(sprite, framebuffer)
+                    .at(
+                        character_part.x as i16,
+                        character_part.y as i16 + self.y_offset,
+                    )
+                    .flip_x(character_part.flip_x)
+                    .flip_y(character_part.flip_y)
+                    .scale(character_part.scale)
+                    .pal_offset(character_part.pal_offset)
+                    .index(index)
+                    .index_map(index_map)
+                    .draw()?;
+            }
             Part::Polygon(polygon_part) => {
                 self.draw_polygon(index, polygon_part, framebuffer, index_map.as_deref_mut());
             }
@@ -207,7 +257,10 @@ impl RoomRenderer {
             dither = dither.rotate_left(1);
             if dither & 1 != 0 {
                 let x = p.x as u16;
-                let y = (p.y + self.y_offset) as u16;
+                let y = p.y.wrapping_add(self.y_offset) as u16;
+                if x >= frame.w() || y >= frame.h() {
+                    return;
+                }
                 frame.set(x, y, color);
                 if let Some(m) = index_map.as_mut() {
                     m.set_index(x, y, index)
@@ -251,7 +304,7 @@ impl RoomRenderer {
         let mut noise_generator = polygon.noise.clone();
         let mut line_color = (polygon.color as u16) << 8;
 
-        for y in 0..final_p.1 - start_p.1 {
+        for y in 0..final_p.1.wrapping_sub(start_p.1).min(200) {
             let mut x0 = left_side[y as usize];
             let mut x1 = right_side[y as usize];
             if x0 > x1 {
@@ -265,20 +318,22 @@ impl RoomRenderer {
                 let x = if !polygon.reverse_gradient {
                     x
                 } else {
-                    x0 + (x1 - x)
+                    x0.wrapping_add(x1.wrapping_sub(x))
                 };
 
-                let y = y + start_p.1 + self.y_offset;
+                let y = y.wrapping_add(start_p.1).wrapping_add(self.y_offset);
 
                 let x = x as u16;
                 let y = y as u16;
-                frame.set(x, y, (rand + (color >> 8) - 1) as u8);
-                if let Some(index_map) = index_map.as_deref_mut() {
-                    index_map.set_index(x, y, index)
+                if x < frame.w() && y < frame.h() {
+                    frame.set(x, y, (rand + (color >> 8)).wrapping_sub(1) as u8);
+                    if let Some(index_map) = index_map.as_deref_mut() {
+                        index_map.set_index(x, y, index)
+                    }
                 }
-                color = color.strict_add_signed(polygon.h_gradient);
+                color = color.wrapping_add_signed(polygon.h_gradient);
             }
-            line_color = line_color.strict_add_signed(polygon.v_gradient);
+            line_color = line_color.wrapping_add_signed(polygon.v_gradient);
         }
     }
 }
@@ -290,6 +345,13 @@ impl Default for RoomRenderer {
 }
 
 fn draw_edge(p0: Point, p1: Point, xs: &mut [i16; 200], xi: &mut usize) {
+    let mut push = |x: i16| {
+        if let Some(slot) = xs.get_mut(*xi) {
+            *slot = x;
+        }
+        *xi += 1;
+    };
+
     let x0 = p0.x;
     let y0 = p0.y;
     let x1 = p1.x;
@@ -302,15 +364,13 @@ fn draw_edge(p0: Point, p1: Point, xs: &mut [i16; 200], xi: &mut usize) {
     }
 
     if dy == 0 {
-        xs[*xi] = i16::min(x0, x1);
-        *xi += 1;
+        push(i16::min(x0, x1));
         return;
     }
 
     if dx == 0 {
         for _ in y0..=y1 {
-            xs[*xi] = x0;
-            *xi += 1;
+            push(x0);
         }
         return;
     }
@@ -334,15 +394,15 @@ fn draw_edge(p0: Point, p1: Point, xs: &mut [i16; 200], xi: &mut usize) {
     }
 
     let mut x0 = x0;
-    let mut ax = major_delta / 2;
+    let mut ax = (major_delta / 2) as u32;
     let mut cx = major_delta;
     loop {
-        ax += minor_delta;
+        ax += minor_delta as u32;
 
         let mut dx;
         let bx;
-        if ax >= major_delta {
-            ax -= major_delta;
+        if ax >= major_delta as u32 {
+            ax -= major_delta as u32;
             dx = bp_4;
             bx = bp_6;
         } else {
@@ -350,11 +410,10 @@ fn draw_edge(p0: Point, p1: Point, xs: &mut [i16; 200], xi: &mut usize) {
             bx = bp_2;
         }
 
-        dx += x0;
+        dx = dx.wrapping_add(x0);
 
         if bx == 1 {
-            xs[*xi] = x0;
-            *xi += 1;
+            push(x0);
         }
 
         x0 = dx;
@@ -369,24 +428,24 @@ fn bresenham_line<F>(p0: Point, p1: Point, mut f: F)
 where
     F: FnMut(Point),
 {
-    let mut x0 = p0.x;
-    let mut y0 = p0.y;
-    let mut x1 = p1.x;
-    let mut y1 = p1.y;
+    let mut x0 = p0.x as i32;
+    let mut y0 = p0.y as i32;
+    let mut x1 = p1.x as i32;
+    let mut y1 = p1.y as i32;
 
     if x0 > x1 {
         swap(&mut x0, &mut x1);
         swap(&mut y0, &mut y1);
     }
 
-    let dx = i16::abs(x1 - x0);
+    let dx = i32::abs(x1 - x0);
     let sx = if x0 < x1 { 1 } else { -1 };
-    let dy = -i16::abs(y1 - y0);
+    let dy = -i32::abs(y1 - y0);
     let sy = if y0 < y1 { 1 } else { -1 };
     let mut error = dx + dy;
 
     loop {
-        f((x0, y0).into());
+        f((x0 as i16, y0 as i16).into());
         if x0 == x1 && y0 == y1 {
             break;
         }
@@ -401,3 +460,85 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Sprite, room_renderer::room::Character};
+
+    #[test]
+    fn test_draw_characters() {
+        let mut room = Room::new();
+        for x in [10, 20] {
+            room.add_part(Part::Character(Character {
+                x,
+                y: 5,
+                flip_x: false,
+                flip_y: false,
+                scale: 0,
+                pal_offset: 0x20,
+            }));
+        }
+
+        let mut character_sheet = SpriteSheet::default();
+        character_sheet.add_sprite(Sprite::encode(&[0x11; 4], 2, 2, 0x10, false).unwrap());
+
+        let mut room_renderer = RoomRenderer::new();
+        room_renderer.set_room(room);
+        room_renderer.set_sprite_sheet(SpriteSheet::default());
+        room_renderer.set_characters(vec![Some(RoomCharacter {
+            sprite_sheet: character_sheet,
+            pose: 0,
+        })]);
+
+        let mut frame = Framebuffer::new(320, 200);
+        let mut index_map = IndexMap::new();
+        room_renderer
+            .draw(&DrawOptions::default(), &mut frame, Some(&mut index_map))
+            .unwrap();
+
+        // The part's pal_offset replaces the sprite's
+        assert_eq!(frame.get(11, 6), 0x21);
+        assert_eq!(index_map.get_index(11, 6), Some(0));
+        // There is no character for the second position
+        assert_eq!(frame.get(20, 5), 0);
+    }
+
+    #[test]
+    fn test_empty_character_position() {
+        let mut room = Room::new();
+        for x in [10, 20] {
+            room.add_part(Part::Character(Character {
+                x,
+                y: 5,
+                flip_x: false,
+                flip_y: false,
+                scale: 0,
+                pal_offset: 0x20,
+            }));
+        }
+
+        let mut character_sheet = SpriteSheet::default();
+        character_sheet.add_sprite(Sprite::encode(&[0x11; 4], 2, 2, 0x10, false).unwrap());
+
+        let mut room_renderer = RoomRenderer::new();
+        room_renderer.set_room(room);
+        room_renderer.set_sprite_sheet(SpriteSheet::default());
+        room_renderer.set_characters(vec![
+            None,
+            Some(RoomCharacter {
+                sprite_sheet: character_sheet,
+                pose: 0,
+            }),
+        ]);
+
+        let mut frame = Framebuffer::new(320, 200);
+        room_renderer
+            .draw(&DrawOptions::default(), &mut frame, None)
+            .unwrap();
+
+        // The second character keeps its position
+        assert_eq!(frame.get(10, 5), 0);
+        assert_eq!(frame.get(21, 6), 0x21);
+    }
+}
@@ -1,11 +1,11 @@
 use serde::Serialize;
 
-use crate::room_renderer::galois_noise_generator::GaloisNoiseGenerator;
+pub use crate::room_renderer::galois_noise_generator::GaloisNoiseGenerator;
 
 #[derive(Clone, Debug, Serialize)]
 #[serde(tag = "type")]
 pub struct Room {
-    _position_marker_count: u8,
+    position_marker_count: u8,
     parts: Vec<Part>,
 }
 
@@ -26,6 +26,9 @@ pub struct Sprite {
 pub struct Character {
     pub x: u16,
     pub y: u8,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub scale: u8,
     pub pal_offset: u8,
 }
 
@@ -48,6 +51,8 @@ pub struct Line {
     pub p1: (i16, i16),
     pub color: u8,
     pub dither: u16,
+    /// Bits 8 to 13 of the command, whose meaning is unknown.
+    pub unknown_flags: u8,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -62,19 +67,35 @@ pub enum Part {
 impl Room {
     pub fn new() -> Self {
         Self {
-            _position_marker_count: 0,
+            position_marker_count: 0,
             parts: Vec::new(),
         }
     }
 
+    pub fn position_marker_count(&self) -> u8 {
+        self.position_marker_count
+    }
+
+    pub fn set_position_marker_count(&mut self, count: u8) {
+        self.position_marker_count = count;
+    }
+
     pub fn parts(&self) -> &[Part] {
         &self.parts
     }
 
+    pub fn parts_mut(&mut self) -> &mut [Part] {
+        &mut self.parts
+    }
+
     pub fn add_part(&mut self, part: Part) {
         self.parts.push(part);
     }
 
+    pub fn insert_part(&mut self, index: usize, part: Part) {
+        self.parts.insert(index, part);
+    }
+
     pub fn remove_part(&mut self, index: usize) {
         self.parts.remove(index);
     }
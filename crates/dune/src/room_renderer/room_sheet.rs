@@ -1,10 +1,10 @@
-use std::io::Cursor;
-
-use bytes_ext::ReadBytesExt;
-
-use crate::room_renderer::{
-    galois_noise_generator::GaloisNoiseGenerator,
-    room::{Character, Line, Part, Polygon, Room, Sprite},
+use crate::{
+    Error,
+    resource_reader::ResourceReader,
+    room_renderer::{
+        galois_noise_generator::GaloisNoiseGenerator,
+        room::{Character, Line, Part, Polygon, Room, Sprite},
+    },
 };
 
 #[derive(Debug)]
@@ -12,26 +12,14 @@ pub struct RoomSheet {
     rooms: Vec<Room>,
 }
 
-#[derive(Debug)]
-pub enum Error {
-    IoError(std::io::Error),
-    FormatError(&'static str),
-}
-
-impl From<std::io::Error> for Error {
-    fn from(error: std::io::Error) -> Self {
-        Self::IoError(error)
-    }
-}
-
 impl RoomSheet {
     pub fn new(data: &[u8]) -> Result<Self, Error> {
-        let mut r = Cursor::new(data);
+        let mut r = ResourceReader::new("room sheet", data);
 
         let room_0_ofs = r.read_le_u16()?;
         let room_count = room_0_ofs / 2;
         if room_count == 0 {
-            return Result::Err(Error::FormatError("invalid room count"));
+            return Err(r.invalid_data("invalid room count"));
         }
 
         let mut room_offsets = Vec::with_capacity(room_count.into());
@@ -43,10 +31,10 @@ impl RoomSheet {
 
         let mut rooms = Vec::with_capacity(room_count.into());
         for ofs in room_offsets {
-            r.set_position(ofs.into());
+            r.set_position(ofs as usize)?;
 
-            let _position_marker_count = r.read_u8()?;
             let mut room = Room::new();
+            room.set_position_marker_count(r.read_u8()?);
 
             loop {
                 let cmd = r.read_le_u16()?;
@@ -59,8 +47,19 @@ impl RoomSheet {
                     let y = r.read_u8()?;
                     let pal_offset = r.read_u8()?;
 
+                    if (cmd & 0x1ff) == 0 {
+                        return Err(r.invalid_data("invalid sprite id"));
+                    }
+
                     if (cmd & 0x1ff) == 1 {
-                        room.add_part(Part::Character(Character { x, y, pal_offset }));
+                        room.add_part(Part::Character(Character {
+                            x,
+                            y,
+                            flip_x: cmd & 0x4000 != 0,
+                            flip_y: cmd & 0x2000 != 0,
+                            scale: ((cmd >> 10) & 7) as u8,
+                            pal_offset,
+                        }));
                     } else {
                         room.add_part(Part::Sprite(Sprite {
                             id: (cmd & 0x1ff) - 1,
@@ -102,6 +101,9 @@ impl RoomSheet {
                         if x & 0x4000 != 0 {
                             break;
                         }
+                        if x < 0 {
+                            return Err(r.invalid_data("unexpected polygon vertex flags"));
+                        }
                     }
 
                     if x >= 0 {
@@ -111,6 +113,9 @@ impl RoomSheet {
 
                             left_vertices.push((x & 0x3fff, y));
 
+                            if x & 0x4000 != 0 {
+                                return Err(r.invalid_data("unexpected polygon vertex flags"));
+                            }
                             if x < 0 {
                                 break;
                             }
@@ -138,6 +143,7 @@ impl RoomSheet {
                         p1,
                         color: (cmd & 0xff) as u8,
                         dither: 0xffffu16,
+                        unknown_flags: ((cmd >> 8) & 0x3f) as u8,
                     }));
                 }
             }
@@ -148,6 +154,31 @@ impl RoomSheet {
         Ok(RoomSheet { rooms })
     }
 
+    /// Serializes the sheet in the format read by [`RoomSheet::new`].
+    /// Fails if the sheet has no rooms, which the format can't represent.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        if self.rooms.is_empty() {
+            return Err(invalid_data(&[], "room sheet without rooms"));
+        }
+
+        let mut data = vec![0; 2 * self.rooms.len()];
+
+        for (i, room) in self.rooms.iter().enumerate() {
+            let Ok(ofs) = u16::try_from(data.len()) else {
+                return Err(invalid_data(&data, "room sheet too large"));
+            };
+            data[2 * i..2 * i + 2].copy_from_slice(&ofs.to_le_bytes());
+
+            data.push(room.position_marker_count());
+            for part in room.parts() {
+                write_part(part, &mut data)?;
+            }
+            data.extend_from_slice(&0xffffu16.to_le_bytes());
+        }
+
+        Ok(data)
+    }
+
     pub fn room_count(&self) -> usize {
         self.rooms.len()
     }
@@ -155,4 +186,249 @@ impl RoomSheet {
     pub fn get_room(&self, room: usize) -> Option<&Room> {
         self.rooms.get(room)
     }
+
+    pub fn get_room_mut(&mut self, room: usize) -> Option<&mut Room> {
+        self.rooms.get_mut(room)
+    }
+
+    /// Appends a room and returns its index.
+    pub fn add_room(&mut self, room: Room) -> usize {
+        self.rooms.push(room);
+        self.rooms.len() - 1
+    }
+}
+
+fn invalid_data(data: &[u8], reason: &'static str) -> Error {
+    Error::InvalidData {
+        resource: "room sheet".to_owned(),
+        offset: data.len(),
+        reason,
+    }
+}
+
+fn write_part(part: &Part, data: &mut Vec<u8>) -> Result<(), Error> {
+    match part {
+        Part::Sprite(sprite) => {
+            if sprite.id == 0 || sprite.id > 0x1fe {
+                return Err(invalid_data(data, "invalid sprite id"));
+            }
+            write_sprite_command(
+                sprite.id + 1,
+                sprite.x,
+                sprite.y,
+                sprite.flip_x,
+                sprite.flip_y,
+                sprite.scale,
+                sprite.pal_offset,
+                data,
+            )
+        }
+        Part::Character(character) => write_sprite_command(
+            1,
+            character.x,
+            character.y,
+            character.flip_x,
+            character.flip_y,
+            character.scale,
+            character.pal_offset,
+            data,
+        ),
+        Part::Polygon(polygon) => write_polygon(polygon, data),
+        Part::Line(line) => {
+            let cmd = 0xc000 | ((line.unknown_flags as u16 & 0x3f) << 8) | line.color as u16;
+            if cmd == 0xffff {
+                return Err(invalid_data(data, "line command is the room terminator"));
+            }
+
+            data.extend_from_slice(&cmd.to_le_bytes());
+            for v in [line.p0.0, line.p0.1, line.p1.0, line.p1.1] {
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+            Ok(())
+        }
+    }
+}
+
+fn write_sprite_command(
+    id: u16,
+    x: u16,
+    y: u8,
+    flip_x: bool,
+    flip_y: bool,
+    scale: u8,
+    pal_offset: u8,
+    data: &mut Vec<u8>,
+) -> Result<(), Error> {
+    if x > 0x1ff {
+        return Err(invalid_data(data, "sprite x out of range"));
+    }
+    if scale > 7 {
+        return Err(invalid_data(data, "invalid sprite scale"));
+    }
+
+    let cmd = (flip_x as u16) << 14
+        | (flip_y as u16) << 13
+        | (scale as u16) << 10
+        | (x & 0x100) << 1
+        | id;
+
+    data.extend_from_slice(&cmd.to_le_bytes());
+    data.extend_from_slice(&[x as u8, y, pal_offset]);
+    Ok(())
+}
+
+fn write_polygon(polygon: &Polygon, data: &mut Vec<u8>) -> Result<(), Error> {
+    let gradient = |g: i16| {
+        i8::try_from(g / 16)
+            .ok()
+            .filter(|_| g % 16 == 0)
+            .ok_or_else(|| invalid_data(data, "invalid polygon gradient"))
+    };
+    let h_gradient = gradient(polygon.h_gradient)?;
+    let v_gradient = gradient(polygon.v_gradient)?;
+
+    let [start, right_vertices @ ..] = polygon.right_vertices.as_slice() else {
+        return Err(invalid_data(data, "polygon without vertices"));
+    };
+    if right_vertices.is_empty() {
+        return Err(invalid_data(data, "polygon without vertices"));
+    }
+    let in_range = |&(x, _): &(i16, i16)| (0..=0x3fff).contains(&x);
+    if !right_vertices
+        .iter()
+        .chain(&polygon.left_vertices)
+        .all(in_range)
+    {
+        return Err(invalid_data(data, "polygon vertex out of range"));
+    }
+
+    let cmd = 0x8000
+        | (polygon.noise.mask & 0x3e00)
+        | (polygon.reverse_gradient as u16) << 8
+        | polygon.color as u16;
+    data.extend_from_slice(&cmd.to_le_bytes());
+    data.extend_from_slice(&[h_gradient as u8, v_gradient as u8]);
+    data.extend_from_slice(&start.0.to_le_bytes());
+    data.extend_from_slice(&start.1.to_le_bytes());
+
+    // The last right vertex is marked with bit 14, and with bit 15 too if
+    // there are no left vertices. The last left vertex is marked with bit 15.
+    let mut write_vertices = |vertices: &[(i16, i16)], last_flags: u16| {
+        for (i, &(x, y)) in vertices.iter().enumerate() {
+            let x = if i == vertices.len() - 1 {
+                x as u16 | last_flags
+            } else {
+                x as u16
+            };
+            data.extend_from_slice(&x.to_le_bytes());
+            data.extend_from_slice(&y.to_le_bytes());
+        }
+    };
+    if polygon.left_vertices.is_empty() {
+        write_vertices(right_vertices, 0xc000);
+    } else {
+        write_vertices(right_vertices, 0x4000);
+        write_vertices(&polygon.left_vertices, 0x8000);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_assets;
+
+    #[test]
+    fn test_malformed_room_sheets() {
+        assert!(matches!(
+            RoomSheet::new(&[0x00, 0x00]),
+            Err(Error::InvalidData { .. })
+        ));
+
+        // Missing 0xffff terminator
+        assert!(matches!(
+            RoomSheet::new(&[0x02, 0x00, 0x00, 0x02, 0x00, 0x10, 0x20, 0x00]),
+            Err(Error::Truncated { .. })
+        ));
+
+        // Sprite id 0
+        assert!(matches!(
+            RoomSheet::new(&[0x02, 0x00, 0x00, 0x00, 0x00, 0x10, 0x20, 0x00, 0xff, 0xff]),
+            Err(Error::InvalidData { .. })
+        ));
+
+        // Polygon vertices with flags that don't end the right or left side
+        for vertices in [
+            [0x05, 0x80, 0x06, 0x00, 0x07, 0xc0, 0x08, 0x00],
+            [0x05, 0x40, 0x06, 0x00, 0x07, 0x40, 0x08, 0x00],
+            [0x05, 0x40, 0x06, 0x00, 0x07, 0xc0, 0x08, 0x00],
+        ] {
+            let mut data = vec![
+                0x02, 0x00, 0x00, 0x34, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ];
+            data.extend_from_slice(&vertices);
+            data.extend_from_slice(&[0x09, 0x80, 0x0a, 0x00, 0xff, 0xff]);
+            assert!(matches!(
+                RoomSheet::new(&data),
+                Err(Error::InvalidData { .. })
+            ));
+        }
+
+        let room_sheet =
+            RoomSheet::new(&[0x02, 0x00, 0x00, 0x02, 0x00, 0x10, 0x20, 0x00, 0xff, 0xff]).unwrap();
+        assert_eq!(room_sheet.room_count(), 1);
+        assert_eq!(room_sheet.get_room(0).unwrap().parts().len(), 1);
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        #[rustfmt::skip]
+        let data = [
+            0x04, 0x00, 0x2d, 0x00,
+            // Room 0: a flipped, scaled sprite at x 300 and a character
+            0x03,
+            0x05, 0x6e, 0x2c, 0x10, 0x20,
+            0x01, 0x00, 0x40, 0x50, 0x00,
+            // A polygon with left vertices
+            0x12, 0xa6, 0x01, 0xfe, 0x0a, 0x00, 0x0b, 0x00,
+            0x14, 0x40, 0x15, 0x00,
+            0x1e, 0x80, 0x1f, 0x00,
+            // A polygon without left vertices
+            0x34, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x05, 0xc0, 0x06, 0x00,
+            0xff, 0xff,
+            // Room 1: a line
+            0x00,
+            0x07, 0xc5, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x04, 0x00,
+            0xff, 0xff,
+        ];
+
+        let mut room_sheet = RoomSheet::new(&data).unwrap();
+        assert_eq!(room_sheet.get_room(0).unwrap().position_marker_count(), 3);
+        assert_eq!(room_sheet.get_room(0).unwrap().parts().len(), 4);
+        assert_eq!(room_sheet.to_bytes().unwrap(), data);
+
+        let room = room_sheet.get_room_mut(1).unwrap();
+        room.remove_part(0);
+        let room_sheet = RoomSheet::new(&room_sheet.to_bytes().unwrap()).unwrap();
+        assert!(room_sheet.get_room(1).unwrap().parts().is_empty());
+
+        let room_sheet = RoomSheet { rooms: Vec::new() };
+        assert!(matches!(
+            room_sheet.to_bytes(),
+            Err(Error::InvalidData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_assets() {
+        for name in ["SIET.SAL", "PALACE.SAL", "VILG.SAL", "HARK.SAL"] {
+            let Some(data) = test_assets::read(name) else {
+                continue;
+            };
+            let room_sheet = RoomSheet::new(&data).unwrap_or_else(|err| panic!("{name}: {err}"));
+            assert_eq!(room_sheet.to_bytes().unwrap(), data, "{name}");
+        }
+    }
 }
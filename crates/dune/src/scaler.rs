@@ -0,0 +1,250 @@
+//! Scaling of framebuffers and other images for display.
+
+use std::borrow::Cow;
+
+use crate::{Error, image::Image};
+
+/// Pixel-art filter applied before nearest-neighbour scaling.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    #[default]
+    Nearest,
+    /// Scale2x, also known as EPX, which smooths diagonal edges without
+    /// adding colors. It is applied for each factor of two in the scale,
+    /// any remaining factor uses nearest-neighbour scaling.
+    Scale2x,
+}
+
+/// Integer scaling with an optional 4:3 aspect correction.
+///
+/// The aspect correction stretches the image vertically by 6/5, so 320x200
+/// fills a 320x240 display like on a CRT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scaler {
+    scale: u16,
+    aspect: bool,
+    filter: Filter,
+}
+
+impl Default for Scaler {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl Scaler {
+    /// A nearest-neighbour scaler. A scale of 0 is treated as 1.
+    pub fn new(scale: u16) -> Self {
+        Self {
+            scale: scale.max(1),
+            aspect: false,
+            filter: Filter::Nearest,
+        }
+    }
+
+    pub fn aspect(mut self, aspect: bool) -> Self {
+        self.aspect = aspect;
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// The size of a scaled `w` x `h` image. Fails with
+    /// [`Error::TooLarge`] if a side doesn't fit in a `u16`.
+    pub fn output_size(&self, w: u16, h: u16) -> Result<(u16, u16), Error> {
+        let w = w as usize * self.scale as usize;
+        let h = h as usize * self.scale as usize;
+        let h = if self.aspect { h * 6 / 5 } else { h };
+
+        match (u16::try_from(w), u16::try_from(h)) {
+            (Ok(w), Ok(h)) => Ok((w, h)),
+            _ => Err(Error::TooLarge {
+                resource: "scaled image".to_owned(),
+                width: w,
+                height: h,
+            }),
+        }
+    }
+
+    /// Scales an image into `dst`, which is only reallocated if its size
+    /// changes.
+    pub fn scale_into<T>(&self, src: &Image<T>, dst: &mut Image<T>) -> Result<(), Error>
+    where
+        T: Copy + Default + PartialEq,
+    {
+        self.scale_map(src, dst, |c| c)
+    }
+
+    pub fn scale<T>(&self, src: &Image<T>) -> Result<Image<T>, Error>
+    where
+        T: Copy + Default + PartialEq,
+    {
+        let mut dst = Image::new(0, 0);
+        self.scale_into(src, &mut dst)?;
+        Ok(dst)
+    }
+
+    /// Scales an image while converting each pixel with `f`, e.g. from a
+    /// palette index to a color.
+    pub(crate) fn scale_map<S, D>(
+        &self,
+        src: &Image<S>,
+        dst: &mut Image<D>,
+        f: impl Fn(S) -> D,
+    ) -> Result<(), Error>
+    where
+        S: Copy + Default + PartialEq,
+        D: Copy + Default,
+    {
+        let (w, h) = self.output_size(src.w, src.h)?;
+        if dst.w != w || dst.h != h {
+            *dst = Image::new(w, h);
+        }
+
+        let (src, scale) = self.apply_filter(src);
+        let scale = scale as usize;
+        let (src_w, src_h) = (src.w as usize, src.h as usize);
+        let (dst_w, dst_h) = (w as usize, h as usize);
+        if dst_w == 0 {
+            return Ok(());
+        }
+
+        for (y, dst_row) in dst.pixels.chunks_exact_mut(dst_w).enumerate() {
+            let src_y = y * src_h / dst_h;
+            let src_row = &src.pixels[src_y * src_w..(src_y + 1) * src_w];
+            for (dst, &c) in dst_row.chunks_exact_mut(scale).zip(src_row) {
+                dst.fill(f(c));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies the filter. Returns the filtered image and the scale left
+    /// for nearest-neighbour scaling.
+    fn apply_filter<'a, T>(&self, src: &'a Image<T>) -> (Cow<'a, Image<T>>, u16)
+    where
+        T: Copy + Default + PartialEq,
+    {
+        let mut image = Cow::Borrowed(src);
+        let mut scale = self.scale;
+
+        if self.filter == Filter::Scale2x {
+            while scale.is_multiple_of(2) {
+                image = Cow::Owned(scale2x(&image));
+                scale /= 2;
+            }
+        }
+
+        (image, scale)
+    }
+}
+
+/// Doubles the size of an image with the Scale2x algorithm.
+pub fn scale2x<T>(src: &Image<T>) -> Image<T>
+where
+    T: Copy + Default + PartialEq,
+{
+    let (w, h) = (src.w as usize, src.h as usize);
+    let mut dst = Image::new(src.w * 2, src.h * 2);
+    let get = |x: usize, y: usize| src.pixels[y * w + x];
+
+    for y in 0..h {
+        for x in 0..w {
+            let p = get(x, y);
+            let a = get(x, y.saturating_sub(1));
+            let b = get((x + 1).min(w - 1), y);
+            let c = get(x.saturating_sub(1), y);
+            let d = get(x, (y + 1).min(h - 1));
+
+            let e0 = if c == a && c != d && a != b { a } else { p };
+            let e1 = if a == b && a != c && b != d { b } else { p };
+            let e2 = if d == c && d != b && c != a { c } else { p };
+            let e3 = if b == d && b != a && d != c { d } else { p };
+
+            let ofs = 2 * y * 2 * w + 2 * x;
+            dst.pixels[ofs] = e0;
+            dst.pixels[ofs + 1] = e1;
+            dst.pixels[ofs + 2 * w] = e2;
+            dst.pixels[ofs + 2 * w + 1] = e3;
+        }
+    }
+
+    dst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Framebuffer;
+
+    fn image(w: u16, h: u16, pixels: &[u8]) -> Framebuffer {
+        let mut image = Framebuffer::new(w, h);
+        image.pixels.copy_from_slice(pixels);
+        image
+    }
+
+    #[test]
+    fn test_nearest() {
+        let src = image(2, 5, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+
+        let dst = Scaler::new(2).scale(&src).unwrap();
+        assert_eq!((dst.w(), dst.h()), (4, 10));
+        assert_eq!(&dst.pixels()[..8], [1, 1, 2, 2, 1, 1, 2, 2]);
+
+        let dst = Scaler::new(1).aspect(true).scale(&src).unwrap();
+        assert_eq!((dst.w(), dst.h()), (2, 6));
+        let rows: Vec<u8> = dst.pixels().chunks(2).map(|row| row[0]).collect();
+        assert_eq!(rows, [1, 1, 3, 5, 7, 9]);
+    }
+
+    #[test]
+    fn test_too_large() {
+        let scaler = Scaler::new(300);
+        assert_eq!(scaler.output_size(218, 1).unwrap(), (65400, 300));
+        assert!(matches!(
+            scaler.output_size(320, 200),
+            Err(Error::TooLarge {
+                width: 96000,
+                height: 60000,
+                ..
+            })
+        ));
+
+        let scaler = Scaler::new(300).aspect(true);
+        assert!(scaler.output_size(1, 200).is_err());
+        assert!(scaler.scale(&Framebuffer::new(1, 200)).is_err());
+    }
+
+    #[test]
+    fn test_scale2x() {
+        // A diagonal edge is smoothed, a straight one isn't
+        #[rustfmt::skip]
+        let src = image(2, 2, &[
+            1, 0,
+            0, 0,
+        ]);
+        #[rustfmt::skip]
+        assert_eq!(scale2x(&src).pixels(), [
+            1, 1, 0, 0,
+            1, 0, 0, 0,
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+        ]);
+
+        let src = image(2, 1, &[1, 0]);
+        assert_eq!(scale2x(&src).pixels(), [1, 1, 0, 0, 1, 1, 0, 0]);
+
+        // Scale 6 is Scale2x followed by 3x nearest-neighbour
+        let scaler = Scaler::new(6).filter(Filter::Scale2x);
+        let dst = scaler.scale(&src).unwrap();
+        assert_eq!((dst.w(), dst.h()), (12, 6));
+        assert_eq!(
+            dst.pixels(),
+            Scaler::new(3).scale(&scale2x(&src)).unwrap().pixels()
+        );
+    }
+}
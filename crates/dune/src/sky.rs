@@ -0,0 +1,183 @@
+use std::io::Cursor;
+
+use crate::{
+    Color, Error, Framebuffer, Palette, Sprite, SpriteSheet, draw_sprite, hsq,
+    resource_reader::ResourceReader,
+};
+
+/// The first resource of the sheet holding a palette.
+const FIRST_PALETTE_RESOURCE: usize = 8;
+/// Size of the header in front of the colors of each palette.
+const PALETTE_HEADER_SIZE: usize = 6;
+/// The palette entries the sky palettes replace.
+const PALETTE_START: usize = 73;
+const PALETTE_COLORS: usize = 151;
+/// The sprites tiled to draw the backdrop, from the top down.
+const BACKDROP_SPRITES: u16 = 4;
+/// Game time steps in a day. The low bits of the game time are the time
+/// of day.
+const GAME_TIME_STEPS_PER_DAY: u16 = 16;
+
+/// The sky resources, SKY.BIN and SKYDN.BIN.
+///
+/// These are sprite sheets whose first resources are the gradient sprites
+/// of the sky and the rest 33 palettes, one for each half step of game
+/// time from midnight to midnight. Each palette holds colors 73 to 223,
+/// shared by the sky and the rooms drawn in front of it.
+pub struct Sky {
+    sprite_sheet: SpriteSheet,
+    palettes: Vec<[Color; PALETTE_COLORS]>,
+}
+
+impl Sky {
+    pub const PALETTE_COUNT: usize = 33;
+
+    pub fn from_possibly_compressed_slice(data: &[u8]) -> Result<Self, Error> {
+        let Ok(header) = hsq::Header::from_reader(&mut Cursor::new(data)) else {
+            return Sky::from_slice(data);
+        };
+
+        if !header.is_compressed() {
+            return Sky::from_slice(data);
+        }
+
+        Sky::from_slice(&hsq::decompress(data)?)
+    }
+
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
+        let sprite_sheet = SpriteSheet::from_slice(data).map_err(|err| err.in_resource("sky"))?;
+
+        let mut r = ResourceReader::new("sky", data);
+        let toc_pos = r.read_le_u16()? as usize;
+
+        let mut palettes = Vec::with_capacity(Self::PALETTE_COUNT);
+        for i in 0..Self::PALETTE_COUNT {
+            r.set_position(toc_pos + (FIRST_PALETTE_RESOURCE + i) * 2)?;
+            let ofs = r.read_le_u16()? as usize;
+            r.set_position(toc_pos + ofs + PALETTE_HEADER_SIZE)?;
+
+            let mut colors = [Color::default(); PALETTE_COLORS];
+            for color in &mut colors {
+                *color = Color(r.read_u8()?, r.read_u8()?, r.read_u8()?);
+            }
+            palettes.push(colors);
+        }
+
+        Ok(Sky {
+            sprite_sheet,
+            palettes,
+        })
+    }
+
+    pub fn sprite_sheet(&self) -> &SpriteSheet {
+        &self.sprite_sheet
+    }
+
+    pub fn gradient_sprite(&self, id: u16) -> Option<&Sprite> {
+        if id as usize >= FIRST_PALETTE_RESOURCE {
+            return None;
+        }
+        self.sprite_sheet.get_sprite(id)
+    }
+
+    /// The colors of a palette, which start at index 73.
+    pub fn palette(&self, index: usize) -> Option<&[Color]> {
+        self.palettes.get(index).map(|colors| colors.as_slice())
+    }
+
+    /// Sets colors 73 to 223 from a sky palette. Indices past the last
+    /// palette use the last one, like the game.
+    pub fn apply_palette(&self, index: usize, pal: &mut Palette) {
+        let colors = &self.palettes[index.min(Self::PALETTE_COUNT - 1)];
+        for (i, &color) in colors.iter().enumerate() {
+            pal.set(PALETTE_START + i, color);
+        }
+    }
+
+    /// Sets colors 73 to 223 for a game time, as stored in savegames.
+    ///
+    /// `fraction` is how far the time is towards the next step, in 256ths,
+    /// and blends the neighbouring palettes for times in between.
+    pub fn apply_game_time(&self, game_time: u16, fraction: u8, pal: &mut Palette) {
+        let time_of_day = (game_time % GAME_TIME_STEPS_PER_DAY) as usize;
+        let position = time_of_day * 512 + fraction as usize * 2;
+        let index = position / 256;
+        let weight = (position % 256) as i16;
+
+        let colors0 = &self.palettes[index];
+        let colors1 = &self.palettes[index + 1];
+        for (i, (c0, c1)) in colors0.iter().zip(colors1).enumerate() {
+            let (r0, g0, b0) = c0.as_i16();
+            let (r1, g1, b1) = c1.as_i16();
+            let color = Color::from_i16(
+                r0 + (r1 - r0) * weight / 256,
+                g0 + (g1 - g0) * weight / 256,
+                b0 + (b1 - b0) * weight / 256,
+            );
+            pal.set(PALETTE_START + i, color);
+        }
+    }
+
+    /// Draws the sky by tiling the gradient sprites across the screen,
+    /// starting at `y`.
+    pub fn draw_backdrop(&self, y: i16, frame: &mut Framebuffer) -> std::io::Result<()> {
+        let mut y = y;
+        for id in 0..BACKDROP_SPRITES {
+            let Some(sprite) = self.gradient_sprite(id) else {
+                continue;
+            };
+
+            for x in (0..frame.w()).step_by(sprite.width() as usize) {
+                draw_sprite(sprite, x as i16, y, frame)?;
+            }
+            y += sprite.height() as i16;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sky() {
+        let mut sheet = SpriteSheet::default();
+        for id in 0..FIRST_PALETTE_RESOURCE as u8 {
+            let pixels = [id + 1; 40 * 20];
+            sheet.add_sprite(Sprite::encode(&pixels, 40, 20, 255, false).unwrap());
+        }
+        for i in 0..Sky::PALETTE_COUNT as u8 {
+            let mut data = vec![0; PALETTE_HEADER_SIZE + 3 * PALETTE_COLORS];
+            data[PALETTE_HEADER_SIZE..].fill(i);
+            sheet.add_resource(data);
+        }
+        let data = sheet.to_bytes().unwrap();
+
+        let sky = Sky::from_slice(&data).unwrap();
+        assert_eq!(sky.palette(3).unwrap()[0], Color(3, 3, 3));
+        assert!(sky.palette(Sky::PALETTE_COUNT).is_none());
+
+        let mut pal = Palette::new();
+        sky.apply_palette(40, &mut pal);
+        assert_eq!(pal.get(72), Color(0, 0, 0));
+        assert_eq!(pal.get(73), Color(32, 32, 32));
+        assert_eq!(pal.get(223), Color(32, 32, 32));
+        assert_eq!(pal.get(224), Color(0, 0, 0));
+
+        sky.apply_game_time(0x123, 0, &mut pal);
+        assert_eq!(pal.get(73), Color(6, 6, 6));
+        sky.apply_game_time(0x12f, 192, &mut pal);
+        assert_eq!(pal.get(73), Color(31, 31, 31));
+
+        let mut frame = Framebuffer::new(320, 200);
+        sky.draw_backdrop(10, &mut frame).unwrap();
+        assert_eq!(frame.get(319, 9), 0);
+        assert_eq!(frame.get(319, 10), 1);
+        assert_eq!(frame.get(0, 89), 4);
+        assert_eq!(frame.get(0, 90), 0);
+
+        assert!(Sky::from_slice(&data[..data.len() - 1]).is_err());
+    }
+}
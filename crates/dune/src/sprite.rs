@@ -1,11 +1,17 @@
 #![allow(clippy::too_many_arguments)]
 
+use crate::{Error, blit};
+
+const FLAG_RLE: u8 = 0x80;
+
 #[derive(Clone)]
 pub struct Sprite {
     width: u16,
     height: u16,
     pal_offset: u8,
     rle: bool,
+    /// The flag bits of the width word, including the RLE flag.
+    flags: u8,
     data: Vec<u8>,
 }
 
@@ -33,8 +39,8 @@ impl std::fmt::Debug for Sprite {
 
 impl Sprite {
     pub fn from_slice(data: &[u8]) -> Option<Self> {
-        let w0 = u16::from_le_bytes(data[0..2].try_into().ok()?);
-        let w1 = u16::from_le_bytes(data[2..4].try_into().ok()?);
+        let w0 = u16::from_le_bytes(data.get(0..2)?.try_into().ok()?);
+        let w1 = u16::from_le_bytes(data.get(2..4)?.try_into().ok()?);
         let data = Vec::from(&data[4..]);
 
         let flags = (w0 & 0xfe00) >> 8;
@@ -46,17 +52,104 @@ impl Sprite {
             return None;
         }
 
-        let rle = (flags & 0x80) != 0;
+        let rle = (flags as u8 & FLAG_RLE) != 0;
 
         Some(Sprite {
             width,
             height,
             pal_offset,
             rle,
+            flags: flags as u8,
             data,
         })
     }
 
+    /// Encodes indexed pixels as a sprite.
+    ///
+    /// Index 0 is transparent. The pixels are packed with 4 bits per pixel
+    /// if all other indices are in `pal_offset + 1..=pal_offset + 15`, and
+    /// otherwise with 8 bits per pixel and a pal_offset of 255. A pal_offset
+    /// of 254 always selects 8 bits per pixel with an opaque index 0.
+    pub fn encode(
+        pixels: &[u8],
+        width: u16,
+        height: u16,
+        pal_offset: u8,
+        rle: bool,
+    ) -> Result<Sprite, Error> {
+        let invalid_data = |reason| Error::InvalidData {
+            resource: "sprite".to_owned(),
+            offset: 0,
+            reason,
+        };
+
+        if width == 0 || width > 0x1ff || height == 0 || height > 0xff {
+            return Err(invalid_data("invalid sprite size"));
+        }
+        if pixels.len() != width as usize * height as usize {
+            return Err(invalid_data("pixel count doesn't match sprite size"));
+        }
+
+        let fits_4bpp = pal_offset < 254
+            && pixels
+                .iter()
+                .all(|&c| c == 0 || (1..=15).contains(&c.wrapping_sub(pal_offset)));
+
+        let (pal_offset, bpp, data) = if fits_4bpp {
+            let pitch = blit::pitch(4, width) as usize;
+            let mut data = vec![0; pitch * height as usize];
+            for (row, src) in data.chunks_mut(pitch).zip(pixels.chunks(width as usize)) {
+                for (x, &c) in src.iter().enumerate() {
+                    let c = if c == 0 {
+                        0
+                    } else {
+                        c.wrapping_sub(pal_offset)
+                    };
+                    row[x / 2] |= c << (4 * (x & 1));
+                }
+            }
+            (pal_offset, 4, data)
+        } else {
+            (
+                if pal_offset == 254 { 254 } else { 255 },
+                8,
+                pixels.to_vec(),
+            )
+        };
+
+        let data = if rle {
+            blit::rle(&data, blit::pitch(bpp, width))
+        } else {
+            data
+        };
+
+        Ok(Sprite {
+            width,
+            height,
+            pal_offset,
+            rle,
+            flags: if rle { FLAG_RLE } else { 0 },
+            data,
+        })
+    }
+
+    /// The palette indices out of `indices` that an opaque pixel of a
+    /// sprite with `pal_offset` can use: any but 0 with 8 bits per pixel,
+    /// any with a pal_offset of 254, and the 15 after `pal_offset` with 4
+    /// bits per pixel.
+    pub fn color_indices(pal_offset: u8, indices: impl IntoIterator<Item = u8>) -> Vec<u8> {
+        indices
+            .into_iter()
+            .filter(|&i| match pal_offset {
+                254 => true,
+                255 => i != 0,
+                pal_offset => i
+                    .checked_sub(pal_offset)
+                    .is_some_and(|c| (1..=15).contains(&c)),
+            })
+            .collect()
+    }
+
     pub fn width(&self) -> u16 {
         self.width
     }
@@ -69,6 +162,11 @@ impl Sprite {
         self.pal_offset
     }
 
+    /// Bits per pixel, 8 for pal_offsets of 254 and 255 and otherwise 4.
+    pub fn bpp(&self) -> u8 {
+        if self.pal_offset >= 254 { 8 } else { 4 }
+    }
+
     pub fn set_pal_offset(&mut self, pal_offset: u8) {
         self.pal_offset = pal_offset;
     }
@@ -77,7 +175,101 @@ impl Sprite {
         self.rle
     }
 
+    /// The flag bits of the sprite header, including the RLE flag 0x80.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Sets the flag bits of the sprite header other than the RLE flag,
+    /// which follows [`Sprite::rle`].
+    pub fn set_flags(&mut self, flags: u8) {
+        self.flags = (flags & 0xfe & !FLAG_RLE) | (self.flags & FLAG_RLE);
+    }
+
     pub fn data(&self) -> &[u8] {
         &self.data
     }
+
+    /// Serializes the sprite in the format read by [`Sprite::from_slice`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let w0 = ((self.flags as u16) << 8) | self.width;
+        let w1 = ((self.pal_offset as u16) << 8) | self.height;
+
+        let mut data = Vec::with_capacity(4 + self.data.len());
+        data.extend_from_slice(&w0.to_le_bytes());
+        data.extend_from_slice(&w1.to_le_bytes());
+        data.extend_from_slice(&self.data);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Framebuffer, draw_sprite};
+
+    fn round_trip(pixels: &[u8], width: u16, height: u16, pal_offset: u8, rle: bool) -> Sprite {
+        let sprite = Sprite::encode(pixels, width, height, pal_offset, rle).unwrap();
+        let sprite = Sprite::from_slice(&sprite.to_bytes()).unwrap();
+
+        let mut framebuffer = Framebuffer::new(width, height);
+        draw_sprite(&sprite, 0, 0, &mut framebuffer).unwrap();
+        assert_eq!(framebuffer.pixels(), pixels);
+
+        sprite
+    }
+
+    #[test]
+    fn test_set_flags() {
+        let mut sprite = Sprite::encode(&[1], 1, 1, 255, true).unwrap();
+        sprite.set_flags(0x03);
+        assert_eq!(sprite.flags(), 0x82);
+        assert_eq!(sprite.to_bytes()[1], 0x82);
+
+        sprite.set_flags(0x80);
+        assert_eq!(sprite.flags(), 0x80);
+    }
+
+    #[test]
+    fn test_color_indices() {
+        assert_eq!(Sprite::color_indices(254, [0, 1, 0xff]), [0, 1, 0xff]);
+        assert_eq!(Sprite::color_indices(255, [0, 1, 0xff]), [1, 0xff]);
+        assert_eq!(
+            Sprite::color_indices(0x20, 0..=255),
+            (0x21..=0x2f).collect::<Vec<u8>>()
+        );
+        assert_eq!(
+            Sprite::color_indices(0xf8, 0..=255),
+            [0xf9, 0xfa, 0xfb, 0xfc, 0xfd, 0xfe, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut pixels = vec![0; 5 * 3];
+        pixels[1] = 0x21;
+        pixels[4] = 0x2f;
+        pixels[7..12].fill(0x25);
+
+        for rle in [false, true] {
+            let sprite = round_trip(&pixels, 5, 3, 0x20, rle);
+            assert_eq!(sprite.pal_offset(), 0x20);
+            assert_eq!(sprite.rle(), rle);
+        }
+        assert_eq!(round_trip(&pixels, 5, 3, 0x20, false).data().len(), 4 * 3);
+
+        // 0x30 is out of range for 4bpp
+        pixels[0] = 0x30;
+        let sprite = round_trip(&pixels, 5, 3, 0x20, false);
+        assert_eq!(sprite.pal_offset(), 255);
+        assert_eq!(sprite.data(), pixels);
+
+        let long: Vec<u8> = (0..300)
+            .map(|x| if x < 200 { 9 } else { x as u8 })
+            .collect();
+        round_trip(&long, 300, 1, 254, true);
+
+        assert!(Sprite::encode(&pixels, 4, 3, 0, false).is_err());
+        assert!(Sprite::encode(&[], 0, 0, 0, false).is_err());
+    }
 }
@@ -1,8 +1,6 @@
 use std::io::Cursor;
 
-use bytes_ext::ReadBytesExt;
-
-use crate::{Palette, hsq, sprite::Sprite};
+use crate::{Error, Palette, hsq, resource_reader::ResourceReader, sprite::Sprite};
 
 enum SpriteOrData {
     Sprite(Sprite),
@@ -17,32 +15,26 @@ pub struct SpriteSheet {
 }
 
 impl SpriteSheet {
-    pub fn from_possibly_compressed_slice(data: &[u8]) -> Result<Self, std::io::Error> {
-        let mut reader = Cursor::new(data);
-        let header = hsq::Header::from_reader(&mut reader)?;
-
-        if !header.is_compressed() {
+    pub fn from_possibly_compressed_slice(data: &[u8]) -> Result<Self, Error> {
+        let Ok(header) = hsq::Header::from_reader(&mut Cursor::new(data)) else {
             return SpriteSheet::from_slice(data);
-        }
+        };
 
-        if header.compressed_size() as usize != data.len() {
-            println!("Packed length does not match resource size");
+        if !header.is_compressed() {
             return SpriteSheet::from_slice(data);
         }
 
-        let mut unpacked_data = vec![0; header.uncompressed_size() as usize];
-        let mut writer = Cursor::new(&mut unpacked_data);
-
-        hsq::unhsq(reader, &mut writer)?;
+        let unpacked_data = hsq::decompress(data)?;
 
         SpriteSheet::from_slice(&unpacked_data)
     }
 
-    pub fn from_slice(data: &[u8]) -> Result<Self, std::io::Error> {
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
         let size = data.len();
+        let mut r = ResourceReader::new("sprite sheet", data);
 
-        let toc_pos = u16::from_le_bytes(data[0..2].try_into().unwrap()) as usize;
-        let mut toc = Cursor::new(&data[toc_pos..]);
+        let toc_pos = r.read_le_u16()? as usize;
+        r.set_position(toc_pos)?;
 
         let pal_update = if toc_pos <= 2 {
             None
@@ -50,17 +42,23 @@ impl SpriteSheet {
             Some(Vec::from(&data[2..toc_pos]))
         };
 
-        let sprite_0_pos = toc.read_le_u16()? as usize;
+        let sprite_0_pos = r.read_le_u16()? as usize;
         let sprite_count = sprite_0_pos / 2;
 
         let mut offsets = Vec::with_capacity(sprite_count);
         let mut prev_pos = sprite_0_pos;
 
         for _ in 1..sprite_count {
-            let pos = toc.read_le_u16()? as usize;
+            let pos = r.read_le_u16()? as usize;
+            if pos < prev_pos || toc_pos + pos > size {
+                return Err(r.bad_offset(toc_pos + pos));
+            }
             offsets.push((toc_pos + prev_pos, pos - prev_pos));
             prev_pos = pos;
         }
+        if toc_pos + prev_pos > size {
+            return Err(r.bad_offset(toc_pos + prev_pos));
+        }
         offsets.push((toc_pos + prev_pos, size - toc_pos - prev_pos));
 
         let mut sprites = Vec::new();
@@ -83,7 +81,39 @@ impl SpriteSheet {
         })
     }
 
-    pub fn apply_palette_update(&self, pal: &mut Palette) -> Result<(), std::io::Error> {
+    /// Serializes the sheet in the format read by [`SpriteSheet::from_slice`].
+    ///
+    /// Resources that aren't sprites are written back unchanged.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let pal_update = self.pal_update.as_deref().unwrap_or_default();
+        let toc_pos = 2 + pal_update.len();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&(toc_pos as u16).to_le_bytes());
+        data.extend_from_slice(pal_update);
+        data.resize(toc_pos + 2 * self.sprites.len(), 0);
+
+        for (i, sprite) in self.sprites.iter().enumerate() {
+            let pos = data.len() - toc_pos;
+            let Ok(pos) = u16::try_from(pos) else {
+                return Err(Error::InvalidData {
+                    resource: "sprite sheet".to_owned(),
+                    offset: data.len(),
+                    reason: "sprite sheet too large",
+                });
+            };
+            data[toc_pos + 2 * i..toc_pos + 2 * i + 2].copy_from_slice(&pos.to_le_bytes());
+
+            match sprite {
+                SpriteOrData::Sprite(sprite) => data.extend_from_slice(&sprite.to_bytes()),
+                SpriteOrData::Data(resource) => data.extend_from_slice(resource),
+            }
+        }
+
+        Ok(data)
+    }
+
+    pub fn apply_palette_update(&self, pal: &mut Palette) -> Result<(), Error> {
         let Some(data) = &self.pal_update else {
             return Ok(());
         };
@@ -93,6 +123,15 @@ impl SpriteSheet {
         Ok(())
     }
 
+    /// The palette update applied by [`SpriteSheet::apply_palette_update`].
+    pub fn palette_update(&self) -> Option<&[u8]> {
+        self.pal_update.as_deref()
+    }
+
+    pub fn set_palette_update(&mut self, pal_update: Option<Vec<u8>>) {
+        self.pal_update = pal_update;
+    }
+
     pub fn resource_count(&self) -> u16 {
         self.resource_count
     }
@@ -105,6 +144,32 @@ impl SpriteSheet {
         }
     }
 
+    /// Appends a sprite and returns its id.
+    pub fn add_sprite(&mut self, sprite: Sprite) -> u16 {
+        self.sprites.push(SpriteOrData::Sprite(sprite));
+        self.resource_count = self.sprites.len() as u16;
+        self.resource_count - 1
+    }
+
+    /// Appends a resource that isn't a sprite and returns its id.
+    pub fn add_resource(&mut self, data: Vec<u8>) -> u16 {
+        self.sprites.push(SpriteOrData::Data(data));
+        self.resource_count = self.sprites.len() as u16;
+        self.resource_count - 1
+    }
+
+    /// Replaces resource `id` with a sprite. Returns false if there is no
+    /// such resource.
+    pub fn set_sprite(&mut self, id: u16, sprite: Sprite) -> bool {
+        match self.sprites.get_mut(id as usize) {
+            Some(resource) => {
+                *resource = SpriteOrData::Sprite(sprite);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn get_resource(&self, id: u16) -> Option<&[u8]> {
         match self.sprites.get(id as usize) {
             Some(SpriteOrData::Data(data)) => Some(data),
@@ -113,3 +178,100 @@ impl SpriteSheet {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_assets;
+
+    #[test]
+    fn test_malformed_sheets() {
+        assert!(matches!(
+            SpriteSheet::from_slice(&[]),
+            Err(Error::Truncated { offset: 0, .. })
+        ));
+        assert!(matches!(
+            SpriteSheet::from_slice(&[0x10, 0x00]),
+            Err(Error::BadOffset { offset: 0x10, .. })
+        ));
+        assert!(matches!(
+            SpriteSheet::from_slice(&[0x02, 0x00, 0x04, 0x00, 0x02, 0x00]),
+            Err(Error::BadOffset { .. })
+        ));
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        #[rustfmt::skip]
+        let data = [
+            // TOC position and palette update
+            0x0a, 0x00, 0x10, 0x01, 0x3f, 0x00, 0x00, 0xff, 0xff, 0xff,
+            // TOC
+            0x06, 0x00, 0x0b, 0x00, 0x0e, 0x00,
+            // 1x1 sprite with the RLE flag and another flag bit set
+            0x01, 0x82, 0x01, 0x10, 0x05,
+            // Data resource
+            0x00, 0x00, 0x07,
+            // 2x1 sprite
+            0x02, 0x00, 0x01, 0x00, 0x12,
+        ];
+
+        let sheet = SpriteSheet::from_slice(&data).unwrap();
+        assert_eq!(sheet.resource_count(), 3);
+        assert!(sheet.get_sprite(0).unwrap().rle());
+        assert_eq!(sheet.get_resource(1), Some(&[0x00, 0x00, 0x07][..]));
+        assert_eq!(sheet.to_bytes().unwrap(), data);
+
+        let data = [0x02, 0x00, 0x02, 0x00, 0x01, 0x00, 0x01, 0x00, 0x33];
+        let sheet = SpriteSheet::from_slice(&data).unwrap();
+        assert_eq!(sheet.to_bytes().unwrap(), data);
+    }
+
+    #[test]
+    fn test_round_trip_assets() {
+        const SPRITE_SHEETS: &[&str] = &[
+            "BALCON.BIN",
+            "BARO.BIN",
+            "BOTA.BIN",
+            "BUNK.BIN",
+            "CHAN.BIN",
+            "COMM.BIN",
+            "CORR.BIN",
+            "EMPR.BIN",
+            "EQUI.BIN",
+            "FEYD.BIN",
+            "FRESK.BIN",
+            "FRM1.BIN",
+            "FRM2.BIN",
+            "FRM3.BIN",
+            "GURN.BIN",
+            "HARA.BIN",
+            "HARK.BIN",
+            "HAWA.BIN",
+            "ICONES.BIN",
+            "IDAH.BIN",
+            "JESS.BIN",
+            "KYNE.BIN",
+            "LETO.BIN",
+            "POR.BIN",
+            "PROUGE.BIN",
+            "SERRE.BIN",
+            "SIET1.BIN",
+            "SKY.BIN",
+            "SKYDN.BIN",
+            "SMUG.BIN",
+            "STIL.BIN",
+            "XPLAIN9.BIN",
+        ];
+
+        for &name in SPRITE_SHEETS {
+            let Some(data) = test_assets::read(name) else {
+                continue;
+            };
+
+            let sheet =
+                SpriteSheet::from_slice(&data).unwrap_or_else(|err| panic!("{name}: {err}"));
+            assert_eq!(sheet.to_bytes().unwrap(), data, "{name}");
+        }
+    }
+}
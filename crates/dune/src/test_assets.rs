@@ -0,0 +1,55 @@
+//! The game files in `assets`, for tests.
+//!
+//! The game's files can't be distributed with the source, so a checkout
+//! may only have empty placeholders. Tests skip files that aren't
+//! available, but anything else wrong with a file panics.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::hsq;
+
+fn dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../assets")
+}
+
+/// The names of all files in `assets`.
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Reads a game file as it is stored. Returns `None` if it is missing or
+/// an empty placeholder.
+pub fn read_raw(name: &str) -> Option<Vec<u8>> {
+    let path = dir().join(name);
+    match fs::read(&path) {
+        Ok(data) if data.is_empty() => {
+            eprintln!("skipping {name}: placeholder");
+            None
+        }
+        Ok(data) => Some(data),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            eprintln!("skipping {name}: not found");
+            None
+        }
+        Err(err) => panic!("{}: {err}", path.display()),
+    }
+}
+
+/// Reads a game file, decompressing it if it is HSQ-compressed.
+pub fn read(name: &str) -> Option<Vec<u8>> {
+    let data = read_raw(name)?;
+    if !hsq::Header::from_reader(&mut &data[..]).is_ok_and(|header| header.is_compressed()) {
+        return Some(data);
+    }
+
+    Some(hsq::decompress(&data).unwrap_or_else(|err| panic!("{name}: {err}")))
+}
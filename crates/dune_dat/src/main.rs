@@ -0,0 +1,273 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use dune::{
+    Error,
+    dat_file::{DatFile, DatWriter},
+    hsq,
+};
+
+/// Lists, unpacks and rebuilds DUNE.DAT archives.
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List every entry with its size, kind and compression.
+    List { dat_file: PathBuf },
+
+    /// Print a summary of the archive.
+    Info { dat_file: PathBuf },
+
+    /// Extract entries into a directory.
+    Extract {
+        dat_file: PathBuf,
+        out_dir: PathBuf,
+
+        /// Decompress HSQ entries. `pack` compresses them again.
+        #[arg(short, long)]
+        decompress: bool,
+
+        /// Only extract these entries.
+        names: Vec<String>,
+    },
+
+    /// Build an archive from a directory created by `extract`.
+    ///
+    /// Entries are written in the order recorded by `extract`, files that
+    /// aren't listed there are appended in alphabetical order.
+    Pack { dir: PathBuf, dat_file: PathBuf },
+
+    /// Check the HSQ header checksum and sizes of every entry.
+    Verify { dat_file: PathBuf },
+}
+
+/// Records the entry order, and which entries were decompressed, so `pack`
+/// can rebuild the archive.
+const MANIFEST_NAME: &str = "dune-dat.txt";
+const MANIFEST_COMPRESS: &str = "compress";
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let result = match args.command {
+        Command::List { dat_file } => list(&dat_file),
+        Command::Info { dat_file } => info(&dat_file),
+        Command::Extract {
+            dat_file,
+            out_dir,
+            decompress,
+            names,
+        } => extract(&dat_file, &out_dir, decompress, &names),
+        Command::Pack { dir, dat_file } => pack(&dir, &dat_file),
+        Command::Verify { dat_file } => verify(&dat_file),
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn list(path: &Path) -> Result<bool, Error> {
+    let mut dat_file = DatFile::open(path)?;
+
+    println!(
+        "{:<16} {:>8} {:>8} {:>8}  {:<4} kind",
+        "name", "offset", "size", "unpacked", "hsq"
+    );
+    let mut ok = true;
+    for e in dat_file.catalog() {
+        let kind = match &e.error {
+            Some(err) => {
+                ok = false;
+                format!("{} (FAILED: {err})", e.kind)
+            }
+            None => e.kind.to_string(),
+        };
+        println!(
+            "{:<16} {:>8x} {:>8} {:>8}  {:<4} {kind}",
+            e.entry.name,
+            e.entry.offset,
+            e.entry.size,
+            e.uncompressed_size,
+            if e.compressed { "yes" } else { "no" },
+        );
+    }
+
+    Ok(ok)
+}
+
+fn info(path: &Path) -> Result<bool, Error> {
+    let mut dat_file = DatFile::open(path)?;
+    let catalog = dat_file.catalog();
+
+    let stored_size: usize = catalog.iter().map(|e| e.entry.size).sum();
+    let unpacked_size: usize = catalog.iter().map(|e| e.uncompressed_size).sum();
+    let compressed_count = catalog.iter().filter(|e| e.compressed).count();
+    let failed_count = catalog.iter().filter(|e| e.error.is_some()).count();
+
+    let mut kinds = BTreeMap::<String, usize>::new();
+    for e in &catalog {
+        *kinds.entry(e.kind.to_string()).or_default() += 1;
+    }
+
+    println!("{}", path.display());
+    println!("  entries:    {}", catalog.len());
+    println!("  compressed: {compressed_count}");
+    println!("  stored:     {stored_size} bytes");
+    println!("  unpacked:   {unpacked_size} bytes");
+    println!("  failed:     {failed_count}");
+    for (kind, count) in kinds {
+        println!("  {kind:<18} {count}");
+    }
+
+    Ok(failed_count == 0)
+}
+
+fn extract(path: &Path, out_dir: &Path, decompress: bool, names: &[String]) -> Result<bool, Error> {
+    let mut dat_file = DatFile::open(path)?;
+    fs::create_dir_all(out_dir)?;
+
+    let entries = dat_file.entries.clone();
+    for name in names {
+        if dat_file.entry(name).is_none() {
+            return Err(Error::NotFound {
+                resource: name.clone(),
+            });
+        }
+    }
+
+    let mut manifest = String::new();
+    for entry in &entries {
+        if !names.is_empty() && !names.contains(&entry.name) {
+            continue;
+        }
+
+        if Path::new(&entry.name).file_name() != Some(entry.name.as_ref()) {
+            eprintln!("Skipping entry `{}` with invalid file name", entry.name);
+            continue;
+        }
+
+        let raw = dat_file.read_raw(&entry.name)?;
+        let compressed = is_compressed(&raw);
+
+        let data = if decompress && compressed {
+            hsq::decompress(&raw).map_err(|err| err.in_resource(&entry.name))?
+        } else {
+            raw
+        };
+
+        fs::write(out_dir.join(&entry.name), data)?;
+
+        manifest.push_str(&entry.name);
+        if decompress && compressed {
+            manifest.push(' ');
+            manifest.push_str(MANIFEST_COMPRESS);
+        }
+        manifest.push('\n');
+
+        println!("{}", entry.name);
+    }
+
+    fs::write(out_dir.join(MANIFEST_NAME), manifest)?;
+
+    Ok(true)
+}
+
+fn pack(dir: &Path, path: &Path) -> Result<bool, Error> {
+    let mut order = Vec::new();
+    let mut compress = Vec::new();
+
+    if let Ok(manifest) = fs::read_to_string(dir.join(MANIFEST_NAME)) {
+        for line in manifest.lines() {
+            let mut fields = line.split_whitespace();
+            let Some(name) = fields.next() else {
+                continue;
+            };
+            order.push(name.to_owned());
+            if fields.next() == Some(MANIFEST_COMPRESS) {
+                compress.push(name.to_owned());
+            }
+        }
+    }
+
+    let mut unlisted = Vec::new();
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        if !dir_entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = dir_entry.file_name().to_string_lossy().into_owned();
+        if name != MANIFEST_NAME && !order.contains(&name) {
+            unlisted.push(name);
+        }
+    }
+    unlisted.sort();
+    order.extend(unlisted);
+
+    let mut writer = DatWriter::new();
+    for name in &order {
+        let data = fs::read(dir.join(name))?;
+        if compress.contains(name) {
+            writer.add_compressed(name, &data)?;
+        } else {
+            writer.add_raw(name, data)?;
+        }
+    }
+
+    writer.write_to_file(path)?;
+    println!(
+        "Wrote {} entries to {}",
+        writer.entry_count(),
+        path.display()
+    );
+
+    Ok(true)
+}
+
+fn verify(path: &Path) -> Result<bool, Error> {
+    let mut dat_file = DatFile::open(path)?;
+
+    let mut ok = true;
+    for entry in dat_file.entries.clone() {
+        let result = dat_file.read_raw(&entry.name).and_then(|raw| {
+            if is_compressed(&raw) || has_hsq_extension(&entry.name) {
+                hsq::decompress(&raw).map_err(|err| err.in_resource(&entry.name))?;
+            }
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => println!("{:<16} ok", entry.name),
+            Err(err) => {
+                println!("{:<16} FAILED: {err}", entry.name);
+                ok = false;
+            }
+        }
+    }
+
+    Ok(ok)
+}
+
+fn is_compressed(data: &[u8]) -> bool {
+    hsq::Header::from_reader(&mut &data[..]).is_ok_and(|header| header.is_compressed())
+}
+
+fn has_hsq_extension(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("hsq") || ext.eq_ignore_ascii_case("sqz"))
+}
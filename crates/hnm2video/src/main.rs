@@ -0,0 +1,194 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::Parser;
+use dune::{
+    Palette, RgbaImage, RgbaOptions,
+    dat_file::DatFile,
+    hnm::HnmDecoder,
+    scaler::{Filter, Scaler},
+};
+
+/// Converts an HNM video to an animated PNG, or a PNG sequence, and a WAV
+/// file with its sound.
+#[derive(Parser, Debug)]
+struct Args {
+    /// HNM file, or the entry name when reading from a DAT file.
+    input: String,
+
+    /// Read the video from this DAT archive.
+    #[arg(short, long)]
+    dat_file: Option<PathBuf>,
+
+    /// Output file for the animated PNG, or the file name prefix for a PNG
+    /// sequence. Defaults to the input name.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Write numbered PNG files instead of an animated PNG.
+    #[arg(long)]
+    sequence: bool,
+
+    /// Frames per second of the animated PNG.
+    #[arg(long, default_value_t = 12)]
+    fps: u16,
+
+    /// Integer scale factor.
+    #[arg(long, default_value_t = 1)]
+    scale: u16,
+
+    /// Stretch frames vertically to a 4:3 display, e.g. 320x200 to 320x240.
+    #[arg(long)]
+    aspect: bool,
+
+    /// Smooth edges with Scale2x for each factor of two in the scale.
+    #[arg(long)]
+    scale2x: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    if let Err(err) = run(&args) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn run(args: &Args) -> std::io::Result<()> {
+    if args.fps == 0 || args.scale == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "fps and scale must be at least 1",
+        ));
+    }
+
+    let data = match &args.dat_file {
+        Some(path) => DatFile::open(path)?.read(&args.input)?,
+        None => std::fs::read(&args.input)?,
+    };
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        let stem = Path::new(&args.input)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_ascii_lowercase();
+        PathBuf::from(stem).with_extension("png")
+    });
+
+    let mut pal = Palette::new();
+    let mut hnm = HnmDecoder::new(&data, &mut pal)?;
+    if hnm.has_undecoded_audio() {
+        eprintln!(
+            "{}: the sound of {} files isn't decoded, only writing the video",
+            args.input,
+            hnm.version()
+        );
+    }
+
+    let filter = if args.scale2x {
+        Filter::Scale2x
+    } else {
+        Filter::Nearest
+    };
+    let options = RgbaOptions {
+        scaler: Scaler::new(args.scale).aspect(args.aspect).filter(filter),
+        ..RgbaOptions::default()
+    };
+    let (width, height) = options.scaler.output_size(hnm.width(), hnm.height())?;
+    let mut apng = if args.sequence {
+        None
+    } else {
+        let w = BufWriter::new(File::create(&output)?);
+        let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(hnm.frame_count() as u32, 0)?;
+        encoder.set_frame_delay(1, args.fps)?;
+        Some(encoder.write_header()?)
+    };
+
+    let mut samples = Vec::new();
+    let mut image = RgbaImage::new(width, height);
+    for frame in hnm.by_ref() {
+        let frame = frame?;
+        if let Some(palette) = frame.palette {
+            pal = palette;
+        }
+        samples.extend_from_slice(&frame.audio);
+
+        frame.framebuffer.write_rgba(&pal, options, &mut image)?;
+
+        if let Some(writer) = apng.as_mut() {
+            writer.write_image_data(image.as_bytes())?;
+        } else {
+            let mut path = output.with_extension("").into_os_string();
+            path.push(format!("-{:04}.png", frame.index));
+            write_png(Path::new(&path), &image)?;
+        }
+    }
+
+    if let Some(writer) = apng {
+        writer.finish()?;
+    }
+
+    if let Some(format) = hnm.audio_format() {
+        write_wav(
+            &output.with_extension("wav"),
+            format.sample_rate,
+            format.channels,
+            &samples,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_png(path: &Path, image: &RgbaImage) -> std::io::Result<()> {
+    let w = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(w, image.w() as u32, image.h() as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_bytes())?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+fn write_wav(path: &Path, sample_rate: u32, channels: u16, samples: &[i16]) -> std::io::Result<()> {
+    const BITS_PER_SAMPLE: u16 = 16;
+
+    let block_align = channels * BITS_PER_SAMPLE / 8;
+    let data_size = (samples.len() * 2) as u32;
+
+    let mut w = BufWriter::new(File::create(path)?);
+    w.write_all(b"RIFF")?;
+    w.write_all(&(36 + data_size).to_le_bytes())?;
+    w.write_all(b"WAVE")?;
+
+    w.write_all(b"fmt ")?;
+    w.write_all(&16u32.to_le_bytes())?;
+    w.write_all(&1u16.to_le_bytes())?;
+    w.write_all(&channels.to_le_bytes())?;
+    w.write_all(&sample_rate.to_le_bytes())?;
+    w.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+    w.write_all(&block_align.to_le_bytes())?;
+    w.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+    w.write_all(b"data")?;
+    w.write_all(&data_size.to_le_bytes())?;
+    for sample in samples {
+        w.write_all(&sample.to_le_bytes())?;
+    }
+
+    w.flush()
+}
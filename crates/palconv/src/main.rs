@@ -0,0 +1,108 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::{Parser, ValueEnum};
+use dune::{Palette, PaletteFormat, SpriteSheet, dat_file::DatFile};
+
+/// Converts palettes between the game's formats and those of paint
+/// programs.
+///
+/// The input is a palette file in any supported format, or a sprite sheet
+/// whose palette update is exported.
+#[derive(Parser, Debug)]
+struct Args {
+    /// Palette or sprite sheet file, or the entry name when reading from a
+    /// DAT file.
+    input: String,
+
+    /// Read the input and the `--palette` sheets from this DAT archive.
+    #[arg(short, long)]
+    dat_file: Option<PathBuf>,
+
+    /// Apply the palette updates of these sheets before the input's, for
+    /// sheets that only update part of the palette.
+    #[arg(short, long)]
+    palette: Vec<String>,
+
+    /// Output file.
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Output format. Defaults to the one of the output's extension, with
+    /// .pal written as JASC-PAL.
+    #[arg(short, long)]
+    format: Option<Format>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    Jasc,
+    Gimp,
+    Act,
+    Vga,
+}
+
+impl From<Format> for PaletteFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Jasc => PaletteFormat::Jasc,
+            Format::Gimp => PaletteFormat::Gimp,
+            Format::Act => PaletteFormat::Act,
+            Format::Vga => PaletteFormat::Vga,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    if let Err(err) = run(&args) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn run(args: &Args) -> std::io::Result<()> {
+    let format = args
+        .format
+        .map(PaletteFormat::from)
+        .or_else(|| {
+            let ext = args.output.extension()?.to_str()?;
+            PaletteFormat::from_extension(ext)
+        })
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "unknown output format, use --format",
+            )
+        })?;
+
+    let mut dat_file = args.dat_file.as_ref().map(DatFile::open).transpose()?;
+    let mut read = |name: &str| -> std::io::Result<Vec<u8>> {
+        match dat_file.as_mut() {
+            Some(dat_file) => Ok(dat_file.read(name)?),
+            None => std::fs::read(name),
+        }
+    };
+
+    let mut pal = Palette::new();
+    for name in &args.palette {
+        SpriteSheet::from_possibly_compressed_slice(&read(name)?)
+            .and_then(|sheet| sheet.apply_palette_update(&mut pal))
+            .map_err(|err| err.in_resource(name))?;
+    }
+
+    let data = read(&args.input)?;
+    if let Some(input_format) = PaletteFormat::detect(&data) {
+        pal = input_format
+            .read(&data)
+            .map_err(|err| err.in_resource(&args.input))?;
+    } else {
+        SpriteSheet::from_possibly_compressed_slice(&data)
+            .and_then(|sheet| sheet.apply_palette_update(&mut pal))
+            .map_err(|err| err.in_resource(&args.input))?;
+    }
+
+    std::fs::write(&args.output, format.write(&pal))
+}
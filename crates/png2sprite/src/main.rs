@@ -0,0 +1,116 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use dune::{Palette, RgbaImage, Sprite, SpriteSheet, dat_file::DatFile};
+
+/// Converts a PNG image to a sprite and adds it to a sprite sheet.
+///
+/// Colors are matched to the closest color in the palette, which is the
+/// palette update of the sheet applied after those of the `--palette`
+/// sheets. Pixels that are more than half transparent become transparent.
+#[derive(Parser, Debug)]
+struct Args {
+    /// The sprite sheet to add the sprite to, e.g. ICONES.HSQ.
+    sheet: PathBuf,
+
+    /// The image to convert.
+    png: PathBuf,
+
+    /// Where to write the new sprite sheet. It is written uncompressed.
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Read the `--palette` sheets from this DAT archive.
+    #[arg(short, long)]
+    dat_file: Option<PathBuf>,
+
+    /// Apply the palette updates of these sheets before the sheet's own,
+    /// for sheets that use colors set by another resource. Without them,
+    /// sprites with 8 bits per pixel only use the colors the sheet sets.
+    #[arg(short, long)]
+    palette: Vec<String>,
+
+    /// Replace this resource instead of appending the sprite.
+    #[arg(long)]
+    id: Option<u16>,
+
+    /// Store the sprite with 4 bits per pixel using the 15 colors after
+    /// this palette index. 254 and 255 store it with 8 bits per pixel, with
+    /// 255 making index 0 transparent.
+    #[arg(long, default_value_t = 255)]
+    pal_offset: u8,
+
+    /// Run-length encode the sprite.
+    #[arg(long)]
+    rle: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    if let Err(err) = run(&args) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn run(args: &Args) -> std::io::Result<()> {
+    let data = std::fs::read(&args.sheet)?;
+    let mut sheet = SpriteSheet::from_possibly_compressed_slice(&data)?;
+
+    let mut pal = Palette::new();
+    let mut dat_file = args.dat_file.as_ref().map(DatFile::open).transpose()?;
+    for name in &args.palette {
+        let data = match dat_file.as_mut() {
+            Some(dat_file) => dat_file.read(name)?,
+            None => std::fs::read(name)?,
+        };
+        SpriteSheet::from_possibly_compressed_slice(&data)
+            .and_then(|sheet| sheet.apply_palette_update(&mut pal))
+            .map_err(|err| err.in_resource(name))?;
+    }
+    sheet.apply_palette_update(&mut pal)?;
+
+    let image = RgbaImage::read_png(&args.png)?;
+    let (width, height) = (image.w(), image.h());
+
+    // Without a full palette, the indices the sheet doesn't set hold
+    // whatever the game loaded before, so they can't be matched
+    let defined: Vec<u8> = match sheet.palette_update() {
+        _ if !args.palette.is_empty() => (0..=255).collect(),
+        Some(update) => Palette::palette_update_indices(update)?,
+        None => Vec::new(),
+    };
+    let candidates = Sprite::color_indices(args.pal_offset, defined);
+    if candidates.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "the sheet sets no colors, use --palette",
+        ));
+    }
+    let pixels = pal.quantize(image.pixels(), &candidates);
+
+    let sprite = Sprite::encode(&pixels, width, height, args.pal_offset, args.rle)?;
+
+    let id = match args.id {
+        Some(id) if sheet.set_sprite(id, sprite.clone()) => id,
+        Some(id) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("the sheet has no resource {id}"),
+            ));
+        }
+        None => sheet.add_sprite(sprite),
+    };
+
+    std::fs::write(&args.output, sheet.to_bytes()?)?;
+    println!(
+        "Wrote sprite {id} ({width}x{height}, pal_offset {}) to {}",
+        sheet.get_sprite(id).map_or(0, Sprite::pal_offset),
+        args.output.display()
+    );
+
+    Ok(())
+}
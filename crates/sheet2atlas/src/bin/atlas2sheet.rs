@@ -0,0 +1,187 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::Parser;
+use dune::{Palette, RgbaImage, Sprite, SpriteSheet, dat_file::DatFile};
+use sheet2atlas::{Atlas, from_hex, read_indexed_png};
+
+/// Builds a sprite sheet from a PNG atlas and the JSON manifest written by
+/// `sheet2atlas`.
+///
+/// The palette indices of an indexed PNG, as written by `sheet2atlas`, are
+/// used as they are. For other PNGs, colors are matched to the closest
+/// color the sprite can use, with pixels that are more than half
+/// transparent becoming transparent.
+#[derive(Parser, Debug)]
+struct Args {
+    /// The JSON manifest.
+    manifest: PathBuf,
+
+    /// Read the `--palette` sheets from this DAT archive.
+    #[arg(short, long)]
+    dat_file: Option<PathBuf>,
+
+    /// Apply the palette updates of these sheets before the manifest's own,
+    /// as when exporting.
+    #[arg(short, long)]
+    palette: Vec<String>,
+
+    /// Output file for the sprite sheet, which is written uncompressed.
+    /// Defaults to the manifest name with a .BIN extension.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+enum AtlasImage {
+    /// Palette indices, used as they are.
+    Indexed(Vec<u8>),
+    Rgba(RgbaImage),
+}
+
+enum Resource {
+    Sprite(Sprite),
+    Data(Vec<u8>),
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    if let Err(err) = run(&args) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn invalid_data(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn run(args: &Args) -> std::io::Result<()> {
+    let atlas: Atlas = serde_json::from_reader(BufReader::new(File::open(&args.manifest)?))?;
+
+    let image_path = args
+        .manifest
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(&atlas.image);
+    let (width, height, image) = match read_indexed_png(&image_path)? {
+        Some((width, height, indices)) => (width, height, AtlasImage::Indexed(indices)),
+        None => {
+            let image = RgbaImage::read_png(&image_path)?;
+            (image.w() as u32, image.h() as u32, AtlasImage::Rgba(image))
+        }
+    };
+    if (width, height) != (atlas.width, atlas.height) {
+        return Err(invalid_data(format!(
+            "{} is {width}x{height}, the manifest expects {}x{}",
+            image_path.display(),
+            atlas.width,
+            atlas.height
+        )));
+    }
+
+    let mut pal = Palette::new();
+    let mut dat_file = args.dat_file.as_ref().map(DatFile::open).transpose()?;
+    for name in &args.palette {
+        let data = match dat_file.as_mut() {
+            Some(dat_file) => dat_file.read(name)?,
+            None => std::fs::read(name)?,
+        };
+        SpriteSheet::from_possibly_compressed_slice(&data)
+            .and_then(|sheet| sheet.apply_palette_update(&mut pal))
+            .map_err(|err| err.in_resource(name))?;
+    }
+
+    let mut sheet = SpriteSheet::default();
+    if let Some(hex) = &atlas.palette_update {
+        let pal_update = from_hex(hex)?;
+        pal.apply_palette_update(&pal_update)?;
+        sheet.set_palette_update(Some(pal_update));
+    }
+
+    let mut resources = BTreeMap::new();
+    for sprite in &atlas.sprites {
+        let x1 = sprite.x + sprite.width as u32;
+        let y1 = sprite.y + sprite.height as u32;
+        if x1 > width || y1 > height {
+            return Err(invalid_data(format!(
+                "sprite {} is outside the image",
+                sprite.id
+            )));
+        }
+
+        let candidates = Sprite::color_indices(sprite.pal_offset, 0..=255);
+        let mut pixels = Vec::with_capacity(sprite.width as usize * sprite.height as usize);
+        for y in sprite.y..y1 {
+            let row = (y * width) as usize;
+            let range = row + sprite.x as usize..row + x1 as usize;
+            match &image {
+                AtlasImage::Indexed(indices) => pixels.extend_from_slice(&indices[range]),
+                AtlasImage::Rgba(image) => {
+                    pixels.extend(pal.quantize(&image.pixels()[range], &candidates));
+                }
+            }
+        }
+
+        let mut encoded = Sprite::encode(
+            &pixels,
+            sprite.width,
+            sprite.height,
+            sprite.pal_offset,
+            sprite.rle,
+        )
+        .map_err(|err| invalid_data(format!("sprite {}: {err}", sprite.id)))?;
+        encoded.set_flags(sprite.flags);
+
+        if resources
+            .insert(sprite.id, Resource::Sprite(encoded))
+            .is_some()
+        {
+            return Err(invalid_data(format!("duplicate resource {}", sprite.id)));
+        }
+    }
+    for data in &atlas.data {
+        let bytes = from_hex(&data.data)?;
+        if bytes.len() != data.size {
+            return Err(invalid_data(format!(
+                "resource {} has {} bytes, the manifest expects {}",
+                data.id,
+                bytes.len(),
+                data.size
+            )));
+        }
+        if resources.insert(data.id, Resource::Data(bytes)).is_some() {
+            return Err(invalid_data(format!("duplicate resource {}", data.id)));
+        }
+    }
+
+    for (expected, (id, resource)) in resources.into_iter().enumerate() {
+        if id as usize != expected {
+            return Err(invalid_data(format!("resource {expected} is missing")));
+        }
+        match resource {
+            Resource::Sprite(sprite) => sheet.add_sprite(sprite),
+            Resource::Data(data) => sheet.add_resource(data),
+        };
+    }
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        let stem = args
+            .manifest
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_ascii_uppercase();
+        PathBuf::from(stem).with_extension("BIN")
+    });
+    std::fs::write(&output, sheet.to_bytes()?)?;
+
+    Ok(())
+}
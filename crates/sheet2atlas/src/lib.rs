@@ -0,0 +1,140 @@
+//! The JSON manifest shared by `sheet2atlas` and `atlas2sheet`.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use dune::Palette;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct Atlas {
+    /// The PNG file, relative to the manifest.
+    pub image: String,
+    pub width: u32,
+    pub height: u32,
+    /// The sheet's palette update as hex bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette_update: Option<String>,
+    pub sprites: Vec<AtlasSprite>,
+    pub data: Vec<AtlasData>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AtlasSprite {
+    pub id: u16,
+    pub x: u32,
+    pub y: u32,
+    pub width: u16,
+    pub height: u16,
+    pub pal_offset: u8,
+    pub rle: bool,
+    /// The flag bits of the sprite header, including the RLE flag 0x80.
+    #[serde(default)]
+    pub flags: u8,
+    /// Informational, the bit depth is chosen from `pal_offset` on import.
+    #[serde(default)]
+    pub bpp: u8,
+}
+
+/// A resource that isn't a sprite.
+#[derive(Serialize, Deserialize)]
+pub struct AtlasData {
+    pub id: u16,
+    pub size: usize,
+    /// The resource as hex bytes.
+    pub data: String,
+}
+
+pub fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn from_hex(hex: &str) -> std::io::Result<Vec<u8>> {
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid hex data");
+
+    if !hex.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+/// Writes palette indices as an indexed PNG with the colors of `pal`, with
+/// index 0 transparent.
+pub fn write_indexed_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    pal: &Palette,
+) -> std::io::Result<()> {
+    let w = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(w, width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(
+        (0..256)
+            .flat_map(|i| {
+                let color = pal.get_rgb888(i);
+                [color.0, color.1, color.2]
+            })
+            .collect::<Vec<u8>>(),
+    );
+    encoder.set_trns(vec![0]);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Reads the palette indices of an indexed PNG. Returns the width, height
+/// and indices, or `None` if the image isn't indexed.
+pub fn read_indexed_png(path: &Path) -> std::io::Result<Option<(u32, u32, Vec<u8>)>> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::IDENTITY);
+
+    let mut reader = decoder.read_info()?;
+    if reader.info().color_type != png::ColorType::Indexed {
+        return Ok(None);
+    }
+    let mut buf = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader.next_frame(&mut buf)?;
+
+    // Images with fewer colors may pack several pixels into a byte
+    let bits = info.bit_depth as usize;
+    let mask = ((1u16 << bits) - 1) as u8;
+    let width = info.width as usize;
+    let mut pixels = Vec::with_capacity(width * info.height as usize);
+    for row in buf.chunks_exact(info.line_size).take(info.height as usize) {
+        pixels.extend((0..width).map(|x| {
+            let bit = x * bits;
+            (row[bit / 8] >> (8 - bits - bit % 8)) & mask
+        }));
+    }
+
+    Ok(Some((info.width, info.height, pixels)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(&[0x00, 0x7f, 0xff]), "007fff");
+        assert_eq!(from_hex("007fFF").unwrap(), [0x00, 0x7f, 0xff]);
+        assert!(from_hex("007").is_err());
+        assert!(from_hex("0g").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::Parser;
+use dune::{Framebuffer, Palette, Sprite, SpriteSheet, dat_file::DatFile, draw_sprite};
+use sheet2atlas::{Atlas, AtlasData, AtlasSprite, to_hex, write_indexed_png};
+
+/// Packs all sprites of a sprite sheet into one PNG, with a JSON file
+/// describing where each sprite is.
+///
+/// The PNG is indexed with the sheet's palette, so `atlas2sheet` gets the
+/// sprites' palette indices back even where the palette repeats a color.
+/// Index 0 is transparent, also in sprites that draw it opaque.
+///
+/// The JSON file also holds the palette update and the resources that
+/// aren't sprites, so `atlas2sheet` can rebuild the sheet.
+#[derive(Parser, Debug)]
+struct Args {
+    /// Sprite sheet file, or the entry name when reading from a DAT file.
+    sheet: String,
+
+    /// Read the sheets from this DAT archive.
+    #[arg(short, long)]
+    dat_file: Option<PathBuf>,
+
+    /// Apply the palette updates of these sheets before the sheet's own,
+    /// for sheets that use colors set by another resource.
+    #[arg(short, long)]
+    palette: Vec<String>,
+
+    /// Output file for the PNG. The JSON file is written next to it.
+    /// Defaults to the sheet name.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Maximum width of the atlas, unless a sprite is wider.
+    #[arg(long, default_value_t = 512)]
+    width: u32,
+
+    /// Space between sprites.
+    #[arg(long, default_value_t = 1)]
+    padding: u32,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    if let Err(err) = run(&args) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn run(args: &Args) -> std::io::Result<()> {
+    let mut dat_file = args.dat_file.as_ref().map(DatFile::open).transpose()?;
+    let mut read_sheet = |name: &str| -> std::io::Result<SpriteSheet> {
+        let data = match dat_file.as_mut() {
+            Some(dat_file) => dat_file.read(name)?,
+            None => std::fs::read(name)?,
+        };
+        Ok(SpriteSheet::from_possibly_compressed_slice(&data)
+            .map_err(|err| err.in_resource(name))?)
+    };
+
+    let mut pal = Palette::new();
+    for name in &args.palette {
+        read_sheet(name)?.apply_palette_update(&mut pal)?;
+    }
+    let sheet = read_sheet(&args.sheet)?;
+    sheet.apply_palette_update(&mut pal)?;
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        let stem = Path::new(&args.sheet)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_ascii_lowercase();
+        PathBuf::from(stem).with_extension("png")
+    });
+
+    let mut sprites = Vec::new();
+    let mut data = Vec::new();
+    for id in 0..sheet.resource_count() {
+        if let Some(sprite) = sheet.get_sprite(id) {
+            sprites.push((id, sprite));
+        } else if let Some(resource) = sheet.get_resource(id) {
+            data.push(AtlasData {
+                id,
+                size: resource.len(),
+                data: to_hex(resource),
+            });
+        }
+    }
+
+    let (width, height, positions) = pack(&sprites, args.width, args.padding);
+
+    let mut pixels = vec![0; width as usize * height as usize];
+    for (&(_, sprite), &(x, y)) in sprites.iter().zip(&positions) {
+        let mut framebuffer = Framebuffer::new(sprite.width(), sprite.height());
+        draw_sprite(sprite, 0, 0, &mut framebuffer)?;
+
+        let row_size = sprite.width() as usize;
+        for (row, src) in framebuffer.pixels().chunks_exact(row_size).enumerate() {
+            let start = (y as usize + row) * width as usize + x as usize;
+            pixels[start..start + row_size].copy_from_slice(src);
+        }
+    }
+    write_indexed_png(&output, width, height, &pixels, &pal)?;
+
+    let atlas = Atlas {
+        image: output
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        width,
+        height,
+        palette_update: sheet.palette_update().map(to_hex),
+        sprites: sprites
+            .iter()
+            .zip(&positions)
+            .map(|(&(id, sprite), &(x, y))| AtlasSprite {
+                id,
+                x,
+                y,
+                width: sprite.width(),
+                height: sprite.height(),
+                pal_offset: sprite.pal_offset(),
+                rle: sprite.rle(),
+                flags: sprite.flags(),
+                bpp: sprite.bpp(),
+            })
+            .collect(),
+        data,
+    };
+    let json = BufWriter::new(File::create(output.with_extension("json"))?);
+    serde_json::to_writer_pretty(json, &atlas)?;
+
+    Ok(())
+}
+
+/// Places the sprites on shelves, tallest first. Returns the size of the
+/// atlas and the position of each sprite.
+fn pack(sprites: &[(u16, &Sprite)], max_width: u32, padding: u32) -> (u32, u32, Vec<(u32, u32)>) {
+    let max_width = sprites
+        .iter()
+        .map(|(_, sprite)| sprite.width() as u32)
+        .fold(max_width, u32::max);
+
+    let mut order: Vec<usize> = (0..sprites.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sprites[i].1.height()));
+
+    let mut positions = vec![(0, 0); sprites.len()];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    let mut width = 0;
+    for i in order {
+        let sprite = sprites[i].1;
+        if x > 0 && x + sprite.width() as u32 > max_width {
+            x = 0;
+            y += shelf_height + padding;
+            shelf_height = 0;
+        }
+
+        positions[i] = (x, y);
+        width = width.max(x + sprite.width() as u32);
+        shelf_height = shelf_height.max(sprite.height() as u32);
+        x += sprite.width() as u32 + padding;
+    }
+
+    (width.max(1), (y + shelf_height).max(1), positions)
+}
@@ -2,7 +2,7 @@
 
 use std::{cell::RefCell, rc::Rc};
 
-use dune::{Framebuffer, Palette, attack::AttackState};
+use dune::{Framebuffer, Palette, RgbaImage, RgbaOptions, attack::AttackState};
 use wasm_bindgen::{Clamped, prelude::*};
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
 
@@ -37,7 +37,7 @@ impl AttackRenderer {
 struct AttackRendererInner {
     attack: AttackState,
     canvas: HtmlCanvasElement,
-    image: Vec<u8>,
+    image: RgbaImage,
     last_frame_time: Option<f64>,
     _frame: usize,
     accumulated_time: f32,
@@ -45,7 +45,7 @@ struct AttackRendererInner {
 
 impl AttackRendererInner {
     pub fn new(canvas: HtmlCanvasElement) -> Rc<RefCell<AttackRendererInner>> {
-        let image = vec![0; 4 * 320 * 200];
+        let image = RgbaImage::new(320, 200);
 
         let attack = AttackState::default();
 
@@ -112,21 +112,12 @@ impl AttackRendererInner {
             .unwrap()
             .dyn_into::<CanvasRenderingContext2d>()?;
 
-        for y in 0..200 {
-            for x in 0..320 {
-                let c = frame.get(x, y);
-                let rgb = pal.get_rgb888(c as usize);
-
-                let ofs = 4 * (y * 320 + x) as usize;
-                self.image[ofs + 0] = rgb.0;
-                self.image[ofs + 1] = rgb.1;
-                self.image[ofs + 2] = rgb.2;
-                self.image[ofs + 3] = 255;
-            }
-        }
+        frame
+            .write_rgba(&pal, RgbaOptions::default(), &mut self.image)
+            .expect("Failed to convert to RGBA");
 
         let image_data =
-            ImageData::new_with_u8_clamped_array_and_sh(Clamped(self.image.as_slice()), 320, 200)
+            ImageData::new_with_u8_clamped_array_and_sh(Clamped(self.image.as_bytes()), 320, 200)
                 .expect("Failed to create ImageData");
 
         context
@@ -2,7 +2,9 @@
 
 use std::{cell::RefCell, rc::Rc};
 
-use dune::{Color, Framebuffer, Palette, SpriteSheet, draw_sprite_from_sheet};
+use dune::{
+    Framebuffer, PaletteFormat, RgbaImage, RgbaOptions, SpriteSheet, draw_sprite_from_sheet,
+};
 use wasm_bindgen::{Clamped, prelude::*};
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
 
@@ -56,7 +58,7 @@ impl GlobeRenderer {
 struct GlobeRendererInner {
     renderer: dune::GlobeRenderer,
     canvas: HtmlCanvasElement,
-    image: Vec<u8>,
+    image: RgbaImage,
     rotation: f32,
     tilt: f32,
     rotation_target: f32,
@@ -79,8 +81,8 @@ fn smallest(a: f32, b: f32) -> f32 {
 
 impl GlobeRendererInner {
     pub fn new(canvas: HtmlCanvasElement) -> Rc<RefCell<GlobeRendererInner>> {
-        let renderer = dune::GlobeRenderer::new(GLOBDATA, MAP, TABLAT);
-        let image = vec![0; 4 * 320 * 200];
+        let renderer = dune::GlobeRenderer::new(GLOBDATA, MAP, TABLAT).unwrap();
+        let image = RgbaImage::new(320, 200);
         let fresk = SpriteSheet::from_slice(FRESK).unwrap();
         let icones = SpriteSheet::from_slice(ICONES).unwrap();
 
@@ -194,16 +196,9 @@ impl GlobeRendererInner {
     }
 
     pub fn draw(&mut self) -> Result<(), JsValue> {
-        let mut pal = Palette::new();
+        let pal = PaletteFormat::Act.read(PAL).unwrap();
         let mut framebuffer = Framebuffer::new(320, 200);
 
-        for i in 0..256 {
-            let r = ((PAL[3 * i + 0] as u32) * 63 / 255) as u8;
-            let g = ((PAL[3 * i + 1] as u32) * 63 / 255) as u8;
-            let b = ((PAL[3 * i + 2] as u32) * 63 / 255) as u8;
-            pal.set(i, Color(r, g, b));
-        }
-
         framebuffer.clear();
 
         self.draw_background(&mut framebuffer);
@@ -223,21 +218,12 @@ impl GlobeRendererInner {
             .unwrap()
             .dyn_into::<CanvasRenderingContext2d>()?;
 
-        for y in 0..200 {
-            for x in 0..320 {
-                let c = framebuffer.get(x, y);
-                let rgb = pal.get_rgb888(c as usize);
-
-                let ofs = 4 * (y * 320 + x) as usize;
-                self.image[ofs + 0] = rgb.0;
-                self.image[ofs + 1] = rgb.1;
-                self.image[ofs + 2] = rgb.2;
-                self.image[ofs + 3] = 255;
-            }
-        }
+        framebuffer
+            .write_rgba(&pal, RgbaOptions::default(), &mut self.image)
+            .expect("Failed to convert to RGBA");
 
         let image_data =
-            ImageData::new_with_u8_clamped_array_and_sh(Clamped(self.image.as_slice()), 320, 200)
+            ImageData::new_with_u8_clamped_array_and_sh(Clamped(self.image.as_bytes()), 320, 200)
                 .expect("Failed to create ImageData");
 
         context.put_image_data(&image_data, 0.0, 0.0).unwrap();
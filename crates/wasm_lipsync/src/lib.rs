@@ -3,7 +3,7 @@
 use core::f32;
 use std::{cell::RefCell, rc::Rc};
 
-use dune::{Framebuffer, Lipsync, Palette, SpriteSheet};
+use dune::{Framebuffer, Lipsync, Palette, RgbaImage, RgbaOptions, SpriteSheet};
 use serde::Serialize;
 use wasm_bindgen::{Clamped, prelude::*};
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData, console};
@@ -60,7 +60,7 @@ impl PortraitRenderer {
             let sprite_sheet = SpriteSheet::from_slice(resource).unwrap();
             let last_resource_id = sprite_sheet.resource_count() - 1;
             let lipsync_data = sprite_sheet.get_resource(last_resource_id).unwrap();
-            let lipsync = Lipsync::from_bytes(lipsync_data);
+            let lipsync = Lipsync::from_bytes(lipsync_data).unwrap();
 
             let name = names.get(i).map(|s| s.to_string()).unwrap_or_default();
             let animation_count = lipsync.animations.len();
@@ -103,7 +103,7 @@ struct PortraitRendererInner {
 
     pal: Palette,
     framebuffer: Framebuffer,
-    image: Vec<u8>,
+    image: RgbaImage,
 
     playback_state: PlaybackState,
     last_frame_time: Option<f64>,
@@ -119,12 +119,12 @@ impl PortraitRendererInner {
     pub fn new(canvas: HtmlCanvasElement) -> Rc<RefCell<PortraitRendererInner>> {
         let mut pal = Palette::new();
         let framebuffer = Framebuffer::new(320, 200);
-        let image = vec![0; 4 * 320 * 200];
+        let image = RgbaImage::new(320, 200);
         let portrait_index = 0;
         let sprite_sheet = SpriteSheet::from_slice(RESOURCES[portrait_index]).unwrap();
         let last_resource_id = sprite_sheet.resource_count() - 1;
         let lipsync_data = sprite_sheet.get_resource(last_resource_id).unwrap();
-        let lipsync = Lipsync::from_bytes(lipsync_data);
+        let lipsync = Lipsync::from_bytes(lipsync_data).unwrap();
 
         let _ = sprite_sheet.apply_palette_update(&mut pal);
 
@@ -188,7 +188,7 @@ impl PortraitRendererInner {
                 let sprite_sheet = SpriteSheet::from_slice(RESOURCES[self.portrait_index]).unwrap();
                 let last_resource_id = sprite_sheet.resource_count() - 1;
                 let lipsync_data = sprite_sheet.get_resource(last_resource_id).unwrap();
-                let lipsync = Lipsync::from_bytes(lipsync_data);
+                let lipsync = Lipsync::from_bytes(lipsync_data).unwrap();
 
                 let _ = sprite_sheet.apply_palette_update(&mut self.pal);
 
@@ -210,12 +210,14 @@ impl PortraitRendererInner {
             return Ok(());
         };
 
-        self.lipsync.draw_animation_frame(
-            &mut self.framebuffer,
-            &self.sprite_sheet,
-            self.animation_index,
-            self.frame_index,
-        );
+        self.lipsync
+            .draw_animation_frame(
+                &mut self.framebuffer,
+                &self.sprite_sheet,
+                self.animation_index,
+                self.frame_index,
+            )
+            .map_err(|err| JsValue::from_str(&err.to_string()))?;
 
         let context_options = serde_wasm_bindgen::to_value(&serde_json::json!({
             "premultipliedAlpha": false,
@@ -230,22 +232,16 @@ impl PortraitRendererInner {
             .unwrap()
             .dyn_into::<CanvasRenderingContext2d>()?;
 
-        for y in 0..200 {
-            for x in 0..320 {
-                let c = self.framebuffer.get(x, y);
-                let rgb = self.pal.get_rgb888(c as usize);
-                let alpha = 255; //self.framebuffer.get_is_set(x, y) as u8 * 255;
-
-                let ofs = 4 * (y * 320 + x) as usize;
-                self.image[ofs + 0] = rgb.0;
-                self.image[ofs + 1] = rgb.1;
-                self.image[ofs + 2] = rgb.2;
-                self.image[ofs + 3] = alpha;
-            }
+        if let Err(err) =
+            self.framebuffer
+                .write_rgba(&self.pal, RgbaOptions::default(), &mut self.image)
+        {
+            console::error_1(&format!("{err:?}").into());
+            return Ok(());
         }
 
         let Ok(image_data) =
-            ImageData::new_with_u8_clamped_array_and_sh(Clamped(self.image.as_slice()), 320, 200)
+            ImageData::new_with_u8_clamped_array_and_sh(Clamped(self.image.as_bytes()), 320, 200)
                 .inspect_err(|err| console::error_1(&format!("{err:?}").into()))
         else {
             return Ok(());
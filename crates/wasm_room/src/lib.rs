@@ -2,7 +2,10 @@
 
 use std::{cell::RefCell, rc::Rc};
 
-use dune::{DrawOptions, Framebuffer, IndexMap, Palette, Room, RoomSheet, SpriteSheet};
+use dune::{
+    DrawOptions, Framebuffer, IndexMap, Palette, RgbaImage, RgbaOptions, Room, RoomCharacter,
+    RoomSheet, Sky, SpriteSheet,
+};
 use serde::Deserialize;
 use wasm_bindgen::{Clamped, prelude::*};
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData, console};
@@ -24,6 +27,24 @@ static SPRITE_SHEET_BUNK: &[u8] = include_bytes!("../../../assets/BUNK.BIN");
 static SPRITE_SHEET_SERRE: &[u8] = include_bytes!("../../../assets/SERRE.BIN");
 static SPRITE_SHEET_BOTA: &[u8] = include_bytes!("../../../assets/BOTA.BIN");
 
+static CHARACTER_LETO: &[u8] = include_bytes!("../../../assets/LETO.BIN");
+static CHARACTER_JESS: &[u8] = include_bytes!("../../../assets/JESS.BIN");
+static CHARACTER_HAWA: &[u8] = include_bytes!("../../../assets/HAWA.BIN");
+static CHARACTER_IDAH: &[u8] = include_bytes!("../../../assets/IDAH.BIN");
+static CHARACTER_GURN: &[u8] = include_bytes!("../../../assets/GURN.BIN");
+static CHARACTER_STIL: &[u8] = include_bytes!("../../../assets/STIL.BIN");
+static CHARACTER_KYNE: &[u8] = include_bytes!("../../../assets/KYNE.BIN");
+static CHARACTER_CHAN: &[u8] = include_bytes!("../../../assets/CHAN.BIN");
+static CHARACTER_HARA: &[u8] = include_bytes!("../../../assets/HARA.BIN");
+static CHARACTER_BARO: &[u8] = include_bytes!("../../../assets/BARO.BIN");
+static CHARACTER_FEYD: &[u8] = include_bytes!("../../../assets/FEYD.BIN");
+static CHARACTER_EMPR: &[u8] = include_bytes!("../../../assets/EMPR.BIN");
+static CHARACTER_HARK: &[u8] = include_bytes!("../../../assets/HARK.BIN");
+static CHARACTER_SMUG: &[u8] = include_bytes!("../../../assets/SMUG.BIN");
+static CHARACTER_FRM1: &[u8] = include_bytes!("../../../assets/FRM1.BIN");
+static CHARACTER_FRM2: &[u8] = include_bytes!("../../../assets/FRM2.BIN");
+static CHARACTER_FRM3: &[u8] = include_bytes!("../../../assets/FRM3.BIN");
+
 static _SKY: &[u8] = include_bytes!("../../../assets/SKY.BIN");
 static SKYDN: &[u8] = include_bytes!("../../../assets/SKYDN.BIN");
 
@@ -35,10 +56,12 @@ struct RoomRenderer {
 #[derive(Debug, Deserialize)]
 struct RoomRendererDrawOptions {
     draw_sprites: bool,
+    draw_characters: bool,
     draw_polygons: bool,
     draw_lines: bool,
     highlighted_index: Option<usize>,
-    sky_palette: Option<usize>,
+    /// The game time in 256ths of a step.
+    time_of_day: Option<u16>,
 }
 
 #[allow(unused)]
@@ -71,6 +94,25 @@ impl RoomRenderer {
         }
     }
 
+    /// Sets the characters drawn at the room's character positions, all
+    /// in the same pose. An unknown name leaves its position empty.
+    pub fn set_characters(&mut self, character_names: Vec<String>, pose: u16) {
+        let characters = character_names
+            .iter()
+            .map(|name| {
+                let sprite_sheet = character_sheet_by_name(name);
+                if sprite_sheet.is_none() {
+                    console::error_1(&format!("unknown character {name:?}").into());
+                }
+                sprite_sheet.map(|sprite_sheet| RoomCharacter { sprite_sheet, pose })
+            })
+            .collect();
+        self.inner
+            .borrow_mut()
+            .room_renderer
+            .set_characters(characters);
+    }
+
     pub fn get_index_of_part_at_position(&self, x: i16, y: i16) -> Option<usize> {
         self.inner.borrow_mut().get_index_of_part_at_position(x, y)
     }
@@ -92,8 +134,9 @@ struct RoomRendererInner {
     room_renderer: dune::RoomRenderer,
     room_sheet: Option<RoomSheet>,
     room_index: usize,
+    sky: Option<Sky>,
     canvas: HtmlCanvasElement,
-    image: Vec<u8>,
+    image: RgbaImage,
     index_map: Option<IndexMap>,
 }
 
@@ -101,13 +144,14 @@ impl RoomRendererInner {
     pub fn new(canvas: HtmlCanvasElement) -> Rc<RefCell<RoomRendererInner>> {
         let room_renderer = dune::RoomRenderer::new();
 
-        let image = vec![0; 4 * 320 * 200];
+        let image = RgbaImage::new(320, 200);
         let index_map = Some(IndexMap::new());
 
         Rc::new(RefCell::new(RoomRendererInner {
             room_renderer,
             room_sheet: None,
             room_index: 0,
+            sky: Sky::from_slice(SKYDN).ok(),
             canvas,
             image,
             index_map,
@@ -159,9 +203,13 @@ impl RoomRendererInner {
             index_map.clear();
         }
 
-        if let Some(sky_palette_index) = options.sky_palette {
+        if let (Some(time_of_day), Some(sky)) = (options.time_of_day, &self.sky) {
             self.room_renderer
-                .draw_sky(SKYDN, sky_palette_index, &mut pal);
+                .set_game_time(time_of_day >> 8, time_of_day as u8);
+            let res = self.room_renderer.draw_sky(sky, &mut pal, &mut frame);
+            if let Err(error) = res {
+                console::error_1(&format!("{error:#?}").into());
+            }
         }
 
         let Some(sprite_sheet) = self.room_renderer.get_sprite_sheet() else {
@@ -169,10 +217,16 @@ impl RoomRendererInner {
         };
 
         sprite_sheet.apply_palette_update(&mut pal).unwrap();
+        for character in self.room_renderer.characters().iter().flatten() {
+            if let Err(error) = character.sprite_sheet.apply_palette_update(&mut pal) {
+                console::error_1(&format!("{error:#?}").into());
+            }
+        }
 
         let res = self.room_renderer.draw(
             &DrawOptions {
                 draw_sprites: options.draw_sprites,
+                draw_characters: options.draw_characters,
                 draw_polygons: options.draw_polygons,
                 draw_lines: options.draw_lines,
             },
@@ -196,31 +250,29 @@ impl RoomRendererInner {
             .unwrap()
             .dyn_into::<CanvasRenderingContext2d>()?;
 
-        for y in 0..200 {
-            for x in 0..320 {
-                let c = frame.get(x, y);
-                let mut rgb = pal.get_rgb888(c as usize);
-
-                if let Some(highlighted_index) = options.highlighted_index
-                    && self.index_map.as_ref().is_some_and(|index_map| {
-                        index_map.get_index(x, y) == Some(highlighted_index)
-                    })
-                {
-                    rgb.0 = (256 - ((256 - rgb.0 as usize) / 2)) as u8;
-                    rgb.1 = (256 - ((256 - rgb.1 as usize) / 2)) as u8;
-                    rgb.2 = (256 - ((256 - rgb.2 as usize) / 2)) as u8;
-                }
+        if let Err(error) = frame.write_rgba(&pal, RgbaOptions::default(), &mut self.image) {
+            console::error_1(&format!("{error:#?}").into());
+            return Ok(());
+        }
 
-                let ofs = 4 * (y * 320 + x) as usize;
-                self.image[ofs + 0] = rgb.0;
-                self.image[ofs + 1] = rgb.1;
-                self.image[ofs + 2] = rgb.2;
-                self.image[ofs + 3] = 255;
+        if let (Some(highlighted_index), Some(index_map)) =
+            (options.highlighted_index, self.index_map.as_ref())
+        {
+            for y in 0..200 {
+                for x in 0..320 {
+                    if index_map.get_index(x, y) == Some(highlighted_index) {
+                        let mut rgba = self.image.get(x, y);
+                        for c in &mut rgba[..3] {
+                            *c = (256 - ((256 - *c as usize) / 2)) as u8;
+                        }
+                        self.image.set(x, y, rgba);
+                    }
+                }
             }
         }
 
         let image_data =
-            ImageData::new_with_u8_clamped_array_and_sh(Clamped(self.image.as_slice()), 320, 200)
+            ImageData::new_with_u8_clamped_array_and_sh(Clamped(self.image.as_bytes()), 320, 200)
                 .expect("Failed to create ImageData");
 
         context
@@ -267,3 +319,28 @@ fn sprite_sheet_by_name(sprite_sheet: &str) -> Option<SpriteSheet> {
 
     SpriteSheet::from_slice(sprite_sheet_data).ok()
 }
+
+fn character_sheet_by_name(character: &str) -> Option<SpriteSheet> {
+    let character_data = match character {
+        "LETO" => CHARACTER_LETO,
+        "JESS" => CHARACTER_JESS,
+        "HAWA" => CHARACTER_HAWA,
+        "IDAH" => CHARACTER_IDAH,
+        "GURN" => CHARACTER_GURN,
+        "STIL" => CHARACTER_STIL,
+        "KYNE" => CHARACTER_KYNE,
+        "CHAN" => CHARACTER_CHAN,
+        "HARA" => CHARACTER_HARA,
+        "BARO" => CHARACTER_BARO,
+        "FEYD" => CHARACTER_FEYD,
+        "EMPR" => CHARACTER_EMPR,
+        "HARK" => CHARACTER_HARK,
+        "SMUG" => CHARACTER_SMUG,
+        "FRM1" => CHARACTER_FRM1,
+        "FRM2" => CHARACTER_FRM2,
+        "FRM3" => CHARACTER_FRM3,
+        _ => return None,
+    };
+
+    SpriteSheet::from_slice(character_data).ok()
+}
@@ -2,7 +2,7 @@
 
 use std::{cell::RefCell, rc::Rc};
 
-use dune::{Framebuffer, Palette, hnm};
+use dune::{Framebuffer, Palette, RgbaImage, RgbaOptions, hnm};
 use wasm_bindgen::{Clamped, prelude::*};
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData, console};
 
@@ -29,7 +29,7 @@ struct HnmVideoRendererInner {
     canvas: HtmlCanvasElement,
     pal: Palette,
     framebuffer: Framebuffer,
-    image: Vec<u8>,
+    image: RgbaImage,
     last_frame_time: Option<f64>,
     frame: usize,
 }
@@ -38,7 +38,7 @@ impl HnmVideoRendererInner {
     pub fn new(canvas: HtmlCanvasElement) -> Rc<RefCell<HnmVideoRendererInner>> {
         let mut pal = Palette::new();
         let framebuffer = Framebuffer::new(320, 200);
-        let image = vec![0; 4 * 320 * 200];
+        let image = RgbaImage::new(320, 200);
         let renderer = hnm::HnmDecoder::new(HNM, &mut pal).unwrap();
 
         let r = Rc::new(RefCell::new(HnmVideoRendererInner {
@@ -101,39 +101,16 @@ impl HnmVideoRendererInner {
             .unwrap()
             .dyn_into::<CanvasRenderingContext2d>()?;
 
-        if false {
-            for y in 0..200 {
-                for x in 0..320 {
-                    let c = if y % 2 == 0 && x % 2 == 0 {
-                        self.framebuffer.get(x / 2, y / 2)
-                    } else {
-                        0
-                    };
-                    let rgb = self.pal.get_rgb888(c as usize);
-
-                    let ofs = 4 * (y * 320 + x) as usize;
-                    self.image[ofs + 0] = rgb.0;
-                    self.image[ofs + 1] = rgb.1;
-                    self.image[ofs + 2] = rgb.2;
-                    self.image[ofs + 3] = 255;
-                }
-            }
-        } else {
-            for y in 0..200 {
-                for x in 0..320 {
-                    let c = self.framebuffer.get(x, y);
-                    let rgb = self.pal.get_rgb888(c as usize);
-                    let ofs = 4 * (y * 320 + x) as usize;
-
-                    self.image[ofs + 0] = rgb.0;
-                    self.image[ofs + 1] = rgb.1;
-                    self.image[ofs + 2] = rgb.2;
-                    self.image[ofs + 3] = 255;
-                }
-            }
+        if let Err(err) =
+            self.framebuffer
+                .write_rgba(&self.pal, RgbaOptions::default(), &mut self.image)
+        {
+            console::error_1(&format!("{err:?}").into());
+            return Ok(());
         }
+
         let Ok(image_data) =
-            ImageData::new_with_u8_clamped_array_and_sh(Clamped(self.image.as_slice()), 320, 200)
+            ImageData::new_with_u8_clamped_array_and_sh(Clamped(self.image.as_bytes()), 320, 200)
                 .inspect_err(|err| console::error_1(&format!("{err:?}").into()))
         else {
             return Ok(());